@@ -0,0 +1,145 @@
+// Authentication as a GitHub App, an alternative to a personal access token.
+//
+// We sign a JWT with the app's private key, and exchange it for an installation
+// token which is used like a personal token. Installation tokens expire after
+// an hour, so we request a new one before then.
+
+use config::AppConfig;
+
+use jsonwebtoken::{self, Algorithm, Header};
+use reqwest::{self, header};
+use std::fs::File;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How long our JWTs are valid for, in seconds (GitHub allows at most ten minutes).
+const JWT_LIFETIME: u64 = 9 * 60;
+// Allowance for the clock on GitHub's servers being behind ours, in seconds.
+const CLOCK_DRIFT: u64 = 60;
+// How long we use an installation token for, in seconds. They are valid for an
+// hour, we stop using them a little early.
+const TOKEN_LIFETIME: u64 = 50 * 60;
+
+/// The current installation token for a GitHub App, if we have one.
+#[derive(Debug, Default)]
+pub struct AppToken {
+    token: Mutex<Option<InstallationToken>>,
+}
+
+#[derive(Clone, Debug)]
+struct InstallationToken {
+    token: String,
+    // Seconds since the Unix epoch.
+    expires: u64,
+}
+
+impl AppToken {
+    /// Get a current installation token, requesting a new one if we don't have
+    /// one or it is about to expire.
+    pub fn get(&self, reqwest: &reqwest::Client, api_base: &str, app: &AppConfig) -> ::Result<String> {
+        // Hold the lock while requesting so that we only request one token at a time.
+        let mut token = self.token.lock().unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if let Some(ref token) = *token {
+            if token.expires > now {
+                return Ok(token.token.clone());
+            }
+        }
+
+        debug!("requesting installation token for app {}", app.app_id);
+        let new_token = request_installation_token(reqwest, api_base, app, now)?;
+        let result = new_token.token.clone();
+        *token = Some(new_token);
+        Ok(result)
+    }
+}
+
+fn request_installation_token(
+    reqwest: &reqwest::Client,
+    api_base: &str,
+    app: &AppConfig,
+    now: u64,
+) -> ::Result<InstallationToken> {
+    let url = format!("{}/app/installations/{}/access_tokens", api_base, app.installation_id);
+    let mut preview = header::Headers::new();
+    preview.set_raw("Accept", "application/vnd.github.machine-man-preview+json");
+
+    let req = reqwest
+        .post(&url)?
+        .header(header::UserAgent::new("nrc"))
+        .header(header::Authorization(format!("Bearer {}", make_jwt(app, now)?)))
+        .headers(preview)
+        .build();
+    debug!("request: `{:?}`", req);
+
+    let mut res = reqwest.execute(req)?;
+    debug!("response: `{:?}`", res);
+
+    if !res.status().is_success() {
+        let mut body = String::new();
+        res.read_to_string(&mut body)?;
+        debug!("body: {}", body);
+        return Err(::WorkErr(format!("Could not get installation token: {:?}", res.status())));
+    }
+
+    let json: TokenResponse = res.json()?;
+    Ok(InstallationToken {
+        token: json.token,
+        expires: now + TOKEN_LIFETIME,
+    })
+}
+
+// Make a JWT identifying the app, signed with its private key.
+fn make_jwt(app: &AppConfig, now: u64) -> ::Result<String> {
+    let mut key = vec![];
+    File::open(&app.private_key_path)?.read_to_end(&mut key)?;
+
+    let claims = Claims {
+        iat: now - CLOCK_DRIFT,
+        exp: now + JWT_LIFETIME,
+        iss: app.app_id,
+    };
+    Ok(jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)?)
+}
+
+#[derive(Debug, Serialize)]
+struct Claims {
+    iat: u64,
+    exp: u64,
+    iss: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cached_token() {
+        let app = AppConfig {
+            app_id: 1,
+            installation_id: 2,
+            private_key_path: "does-not-exist.der".to_owned(),
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let app_token = AppToken::default();
+        *app_token.token.lock().unwrap() = Some(InstallationToken {
+            token: "abc".to_owned(),
+            expires: now + 60,
+        });
+
+        // An unexpired token is used without making a request.
+        let reqwest = reqwest::Client::new().unwrap();
+        let token = app_token.get(&reqwest, "http://localhost:1", &app).unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(token, "abc");
+
+        // An expired token must be replaced, which fails without a key.
+        app_token.token.lock().unwrap().as_mut().unwrap().expires = now - 1;
+        assert!(app_token.get(&reqwest, "http://localhost:1", &app).is_err());
+    }
+}
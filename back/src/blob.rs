@@ -1,12 +1,24 @@
-use data::{StructuralData, Link};
+use config::Config;
+use data::{StructuralData, Link, Tag};
+use date;
 use github::Issue;
 use issues::IssueData;
+use query::Filter;
 
 use std::collections::HashSet;
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Blob {
     pub tabs: Vec<Tab>,
+    /// Tabs which are not listed, but can be found by id.
+    #[serde(skip)]
+    pub hidden_tabs: Vec<Tab>,
+    /// Definitions of the tags used by categories.
+    pub tags: Vec<Tag>,
+    /// The commit the structural data was read from, if known.
+    pub data_sha: Option<String>,
+    /// Where the structural data was read from.
+    pub data_source: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -30,11 +42,22 @@ pub struct Category {
 
 impl Blob {
     /// Make a blob from the strucrtural data and issues we've pulled from GitHub.
-    pub fn make(struct_data: &StructuralData, issues: &IssueData) -> ::Result<Blob> {
-        let mut result = Blob { tabs: vec![] };
+    pub fn make(config: &Config, struct_data: &StructuralData, issues: &IssueData) -> ::Result<Blob> {
+        let mut result = Blob {
+            tabs: vec![],
+            hidden_tabs: vec![],
+            tags: struct_data.tags.clone(),
+            data_sha: struct_data.sha.clone(),
+            data_source: struct_data.source.clone(),
+        };
+
+        // Tabs which have expired (or not started yet) are left out.
+        let now = date::now();
+        let mut raw_tabs: Vec<_> = struct_data.tabs.iter().filter(|t| t.is_active(&now)).collect();
+        raw_tabs.sort_by_key(|t| t.order);
 
         // Iterate over tabs, a raw tab becomes a blob tab.
-        for t in &struct_data.tabs {
+        for t in raw_tabs {
             let mut tab = Tab {
                 id: t.id.clone(),
                 title: t.title.clone(),
@@ -49,22 +72,25 @@ impl Blob {
             // Iterate over the categories in each tab.
             if let Some(tcs) = struct_data.tab_category.get(&t.id) {
                 for tc in tcs {
-                    assert!(tc.tab == t.id);
-                    let cat = &struct_data.categories[&tc.category];
-                    assert!(cat.id == tc.category);
+                    // Validation should have removed any bad entries, but
+                    // skip them anyway rather than crash.
+                    if tc.tab != t.id {
+                        continue;
+                    }
+                    let cat = match struct_data.categories.get(&tc.category) {
+                        Some(cat) => cat,
+                        None => continue,
+                    };
 
                     // If there are no issues, don't list the category.
-                    if let Some(issues) = issues.issues.get(&(tc.tab.clone(), tc.category.clone())) {
-                        assert!(!issues.is_empty());
+                    let issues = issues.issues.get(&(tc.tab.clone(), tc.category.clone())).filter(|i| !i.is_empty());
+                    if let Some(issues) = issues {
                         // Merge the various links into a single list.
                         let links = tc
                             .link
                             .iter()
                             .cloned()
-                            .chain(Some(Link {
-                                text: "repository".to_owned(),
-                                url: format!("https://github.com/{}", cat.repository),
-                            }).into_iter())
+                            .chain(cat.repository_links(config))
                             .chain(cat.links.iter().cloned())
                             .collect();
                         let category = Category {
@@ -84,13 +110,18 @@ impl Blob {
             tab.tags = tags.into_iter().cloned().collect();
             tab.tags.sort();
 
-            result.tabs.push(tab);
+            if t.hidden {
+                result.hidden_tabs.push(tab);
+            } else {
+                result.tabs.push(tab);
+            }
         }
         Ok(result)
     }
 
     /// Return a view of the blob data which includes all the tabs, but only the
-    /// specified one contains data.
+    /// specified one contains data. A hidden tab is added to the end of the
+    /// tabs if it is asked for by id.
     pub fn by_tab(&self, tab: &str) -> ::Result<Blob> {
         use std::str::FromStr;
 
@@ -113,10 +144,14 @@ impl Blob {
             _ => TabIdentifier::Id(tab),
         };
 
-        if !self.tabs.iter().enumerate().any(|t| tab_id.matches(t)) {
+        let hidden = match tab_id {
+            TabIdentifier::Id(id) => self.hidden_tabs.iter().find(|t| t.id == id),
+            TabIdentifier::Index(_) => None,
+        };
+        if hidden.is_none() && !self.tabs.iter().enumerate().any(|t| tab_id.matches(t)) {
             return Err(::WorkErr(format!("tab not found: {}", tab)));
         }
-        let tabs = self.tabs.iter().enumerate().map(|t| {
+        let mut tabs: Vec<Tab> = self.tabs.iter().enumerate().map(|t| {
             let mut tab_clone = t.1.clone();
             if tab_id.matches(t) {
                 tab_clone
@@ -126,17 +161,39 @@ impl Blob {
                 tab_clone
             }
         }).collect();
-        Ok(Blob { tabs })
+        tabs.extend(hidden.cloned());
+        Ok(Blob {
+            tabs,
+            hidden_tabs: vec![],
+            tags: self.tags.clone(),
+            data_sha: self.data_sha.clone(),
+            data_source: self.data_source.clone(),
+        })
+    }
+
+    /// Remove any categories and issues which don't match `filter`, and any
+    /// categories left without issues. Each tab's list of tags is left alone,
+    /// so clients can still offer the other tags.
+    pub fn filter(mut self, filter: &Filter) -> Blob {
+        for tab in &mut self.tabs {
+            tab.categories.retain(|c| filter.matches_category(c));
+            for category in &mut tab.categories {
+                category.issues.retain(|i| filter.matches_issue(i));
+            }
+            tab.categories.retain(|c| !c.issues.is_empty());
+        }
+        self
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use mock::{mock_issue_data, mock_struct_data};
+    use mock::{mock_config, mock_issue_data, mock_struct_data};
+    use query;
 
     fn make_blob() -> Blob {
-        Blob::make(&mock_struct_data(), &mock_issue_data()).unwrap_or_else(|s| panic!("{:?}", s))
+        Blob::make(&mock_config(), &mock_struct_data(), &mock_issue_data()).unwrap_or_else(|s| panic!("{:?}", s))
     }
 
     fn assert_foo_props(tab: &Tab) {
@@ -146,6 +203,7 @@ mod test {
         assert_eq!(tab.categories.len(), 1);
         let cat = &tab.categories[0];
         assert_eq!(cat.title, "Rustfmt");
+        assert_eq!(cat.links[0].url, "https://github.com/rust-lang-nursery/rustfmt");
     }
 
     #[test]
@@ -157,12 +215,60 @@ mod test {
         assert_eq!(blob.tabs[1].categories.len(), 1);
     }
 
+    #[test]
+    fn test_make_no_issues() {
+        let mut issues = mock_issue_data();
+        for v in issues.issues.values_mut() {
+            v.clear();
+        }
+        let blob = Blob::make(&mock_config(), &mock_struct_data(), &issues).unwrap_or_else(|s| panic!("{:?}", s));
+        assert!(blob.tabs.iter().all(|t| t.categories.is_empty()));
+    }
+
     #[test]
     fn test_by_tab() {
-        let blob = make_blob();
+        let mut blob = make_blob();
+        blob.data_sha = Some("abc".to_owned());
         let blob_foo = blob.by_tab("foo").unwrap_or_else(|s| panic!("{:?}", s));
         assert_foo_props(&blob_foo.tabs[0]);
         assert_eq!(blob_foo.tabs[1].categories.len(), 0);
+        assert_eq!(blob_foo.data_sha, blob.data_sha);
+    }
+
+    #[test]
+    fn test_filter() {
+        let blob = make_blob().filter(&Filter::default().with_tags(&["a".to_owned()]));
+        assert_foo_props(&blob.tabs[0]);
+        let blob = blob.filter(&query::parse("author:nrc category:rustfmt").unwrap());
+        assert_foo_props(&blob.tabs[0]);
+
+        let blob = blob.filter(&Filter::default().with_tags(&["a".to_owned(), "c".to_owned()]));
+        assert_eq!(blob.tabs[0].categories.len(), 0);
+        assert_eq!(blob.tabs[0].tags.len(), 2);
+
+        // Categories without any matching issues are removed.
+        let blob = make_blob().filter(&query::parse("-author:nrc").unwrap());
+        assert_eq!(blob.tabs[0].categories.len(), 0);
+    }
+
+    #[test]
+    fn test_tab_visibility() {
+        let mut struct_data = mock_struct_data();
+        struct_data.tabs[0].order = 1;
+        let blob = Blob::make(&mock_config(), &struct_data, &mock_issue_data()).unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(blob.tabs[0].id, "bar");
+        assert_foo_props(&blob.tabs[1]);
+
+        struct_data.tabs[0].active_until = Some("2017-12-18".to_owned());
+        struct_data.tabs[1].hidden = true;
+        let blob = Blob::make(&mock_config(), &struct_data, &mock_issue_data()).unwrap_or_else(|s| panic!("{:?}", s));
+        assert!(blob.tabs.is_empty());
+        assert_eq!(blob.hidden_tabs.len(), 1);
+        assert!(blob.by_tab("foo").is_err());
+        assert!(blob.by_tab("0").is_err());
+        let blob_bar = blob.by_tab("bar").unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(blob_bar.tabs.len(), 1);
+        assert_eq!(blob_bar.tabs[0].categories.len(), 1);
     }
 
     #[test]
@@ -0,0 +1,208 @@
+// Compressing responses, if the client accepts it.
+
+use brotli2::write::BrotliEncoder;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use hyper::header::{self, q, AcceptEncoding};
+use hyper::mime::{self, Mime};
+
+use std::io::Write;
+
+// Compression levels for bodies compressed once and served many times, and
+// for those compressed for a single response.
+const GZIP_BEST: u32 = 9;
+const GZIP_FAST: u32 = 6;
+const BROTLI_BEST: u32 = 11;
+const BROTLI_FAST: u32 = 5;
+
+/// A content coding for a response body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    /// The best encoding the client accepts, according to its
+    /// `Accept-Encoding` header. We prefer brotli if the client has no
+    /// preference.
+    pub fn negotiate(accept: Option<&AcceptEncoding>) -> Encoding {
+        let accept = match accept {
+            Some(accept) => accept,
+            None => return Encoding::Identity,
+        };
+        let quality = |encoding: header::Encoding| {
+            accept
+                .iter()
+                .find(|i| i.item == encoding)
+                .or_else(|| accept.iter().find(|i| i.item == header::Encoding::EncodingExt("*".to_owned())))
+                .map_or(q(0), |i| i.quality)
+        };
+
+        let brotli = quality(header::Encoding::Brotli);
+        let gzip = quality(header::Encoding::Gzip);
+        if brotli > q(0) && brotli >= gzip {
+            Encoding::Brotli
+        } else if gzip > q(0) {
+            Encoding::Gzip
+        } else {
+            Encoding::Identity
+        }
+    }
+
+    /// The value for the `Content-Encoding` header, if one is needed.
+    pub fn header(self) -> Option<header::Encoding> {
+        match self {
+            Encoding::Identity => None,
+            Encoding::Gzip => Some(header::Encoding::Gzip),
+            Encoding::Brotli => Some(header::Encoding::Brotli),
+        }
+    }
+
+    /// Distinguishes the ETags of different encodings of the same body.
+    pub fn etag_suffix(self) -> &'static str {
+        match self {
+            Encoding::Identity => "",
+            Encoding::Gzip => "-gzip",
+            Encoding::Brotli => "-br",
+        }
+    }
+}
+
+/// A response body with compressed copies of it, so that they are only made
+/// once.
+#[derive(Clone, Debug)]
+pub struct Precompressed {
+    identity: Vec<u8>,
+    gzip: Option<Vec<u8>>,
+    brotli: Option<Vec<u8>>,
+}
+
+impl Precompressed {
+    /// Compress `bytes` as well as we can, since the result will be reused.
+    pub fn new(bytes: Vec<u8>) -> ::Result<Precompressed> {
+        Ok(Precompressed {
+            gzip: Some(gzip(&bytes, GZIP_BEST)?),
+            brotli: Some(brotli(&bytes, BROTLI_BEST)?),
+            identity: bytes,
+        })
+    }
+
+    /// A body without compressed copies, any will be made when needed.
+    pub fn identity(bytes: Vec<u8>) -> Precompressed {
+        Precompressed {
+            identity: bytes,
+            gzip: None,
+            brotli: None,
+        }
+    }
+
+    /// The body in `encoding`, compressing it now if we don't have a copy.
+    pub fn encoded(&self, encoding: Encoding) -> ::Result<Vec<u8>> {
+        let copy = match encoding {
+            Encoding::Identity => Some(&self.identity),
+            Encoding::Gzip => self.gzip.as_ref(),
+            Encoding::Brotli => self.brotli.as_ref(),
+        };
+        match copy {
+            Some(bytes) => Ok(bytes.clone()),
+            None => compress(&self.identity, encoding),
+        }
+    }
+}
+
+/// Compress `bytes` for a single response.
+pub fn encode(bytes: Vec<u8>, encoding: Encoding) -> ::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Identity => Ok(bytes),
+        _ => compress(&bytes, encoding),
+    }
+}
+
+fn compress(bytes: &[u8], encoding: Encoding) -> ::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Identity => Ok(bytes.to_owned()),
+        Encoding::Gzip => gzip(bytes, GZIP_FAST),
+        Encoding::Brotli => brotli(bytes, BROTLI_FAST),
+    }
+}
+
+/// Whether responses with this content type are worth compressing: text,
+/// JSON, JavaScript, and XML (e.g., feeds). Images and fonts are already
+/// compressed.
+pub fn is_compressible(content_type: &Mime) -> bool {
+    content_type.type_() == mime::TEXT
+        || content_type.subtype() == mime::JSON
+        || content_type.subtype() == mime::JAVASCRIPT
+        || content_type.subtype() == mime::XML
+        || content_type.suffix() == Some(mime::XML)
+}
+
+fn gzip(bytes: &[u8], level: u32) -> ::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len() / 4), Compression::new(level));
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+fn brotli(bytes: &[u8], level: u32) -> ::Result<Vec<u8>> {
+    let mut encoder = BrotliEncoder::new(Vec::with_capacity(bytes.len() / 4), level);
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use brotli2::read::BrotliDecoder;
+    use flate2::read::GzDecoder;
+    use hyper::header::{qitem, QualityItem};
+    use std::io::Read;
+
+    fn accept(items: Vec<QualityItem<header::Encoding>>) -> Encoding {
+        Encoding::negotiate(Some(&AcceptEncoding(items)))
+    }
+
+    #[test]
+    fn test_negotiate() {
+        use hyper::header::Encoding::*;
+
+        assert_eq!(Encoding::negotiate(None), Encoding::Identity);
+        assert_eq!(accept(vec![]), Encoding::Identity);
+        assert_eq!(accept(vec![qitem(Gzip), qitem(Deflate), qitem(Brotli)]), Encoding::Brotli);
+        assert_eq!(accept(vec![qitem(Gzip), qitem(Deflate)]), Encoding::Gzip);
+        assert_eq!(accept(vec![qitem(Gzip), QualityItem::new(Brotli, q(500))]), Encoding::Gzip);
+        assert_eq!(accept(vec![qitem(EncodingExt("*".to_owned())), QualityItem::new(Brotli, q(0))]), Encoding::Gzip);
+        assert_eq!(accept(vec![QualityItem::new(Gzip, q(0)), qitem(Identity)]), Encoding::Identity);
+    }
+
+    #[test]
+    fn test_precompressed() {
+        let text = "Find something Rusty to work on. ".repeat(100).into_bytes();
+        let body = Precompressed::new(text.clone()).unwrap();
+
+        let gzip = body.encoded(Encoding::Gzip).unwrap();
+        assert!(gzip.len() < text.len() / 10);
+        let mut decoded = vec![];
+        GzDecoder::new(&*gzip).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, text);
+
+        let brotli = body.encoded(Encoding::Brotli).unwrap();
+        let mut decoded = vec![];
+        BrotliDecoder::new(&*brotli).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, text);
+
+        assert_eq!(body.encoded(Encoding::Identity).unwrap(), text);
+        assert_eq!(Precompressed::identity(text.clone()).encoded(Encoding::Gzip).unwrap().len(), encode(text.clone(), Encoding::Gzip).unwrap().len());
+    }
+
+    #[test]
+    fn test_is_compressible() {
+        assert!(is_compressible(&mime::APPLICATION_JSON));
+        assert!(is_compressible(&mime::TEXT_HTML));
+        assert!(is_compressible(&mime::TEXT_CSS));
+        assert!(is_compressible(&"application/javascript".parse().unwrap()));
+        assert!(is_compressible(&"application/atom+xml".parse().unwrap()));
+        assert!(!is_compressible(&mime::IMAGE_PNG));
+    }
+}
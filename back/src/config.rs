@@ -1,24 +1,180 @@
+use github::DEFAULT_API_BASE;
+
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 
 use serde_json;
 
-const CONFIG_PATH: &'static str = "../data/config.json";
+pub const CONFIG_PATH: &'static str = "../data/config.json";
+/// Where the structural data is read from in dev mode, by default.
+pub const LOCAL_DATA_PATH: &str = "../data";
 
 /// Configuration for the server.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     pub repository: String,
+    /// The branch, tag, or commit of `repository` to read the structural data
+    /// from. Defaults to the repository's default branch.
+    #[serde(default)]
+    pub data_ref: Option<String>,
+    /// Where to read the structural data from, each is tried in order until
+    /// one succeeds. See `data_sources()` for the default.
+    #[serde(default)]
+    pub data_sources: Vec<DataSource>,
+    /// Not needed if authenticating as a GitHub App.
+    #[serde(default)]
     pub username: String,
+    /// Not needed if authenticating as a GitHub App.
+    #[serde(default)]
     pub token: String,
+    /// Authenticate as a GitHub App rather than using `token`.
+    #[serde(default)]
+    pub app: Option<AppConfig>,
     pub addr: String,
     pub static_path: String,
     pub index_path: String,
     pub dev_mode: bool,
+    /// Base url of the GitHub API, change this for GitHub Enterprise.
+    #[serde(default="default_api_base")]
+    pub api_base: String,
+    /// Tokens for GitHub instances other than `api_base`, keyed by API base url.
+    #[serde(default)]
+    pub api_tokens: HashMap<String, String>,
+    /// Where to save cached GitHub responses between runs.
+    #[serde(default)]
+    pub cache_path: Option<String>,
+    /// Which GitHub API to use for fetching issues.
+    #[serde(default)]
+    pub backend: Backend,
+    /// Whether to leave out assigned issues, unless a tab-category says otherwise.
+    #[serde(default)]
+    pub hide_assigned: bool,
+}
+
+impl Config {
+    /// The sources of structural data to try, in order. If none are configured,
+    /// then the local data directory in dev mode, otherwise the `data`
+    /// directory of `repository` at `data_ref`.
+    pub fn data_sources(&self) -> Vec<DataSource> {
+        if !self.data_sources.is_empty() {
+            return self.data_sources.clone();
+        }
+
+        if self.dev_mode {
+            vec![DataSource::Local { path: LOCAL_DATA_PATH.to_owned() }]
+        } else {
+            vec![DataSource::GitHub {
+                repository: self.repository.clone(),
+                git_ref: self.data_ref.clone(),
+            }]
+        }
+    }
+
+    // Check for settings which can be read, but can't work.
+    fn check(&self) -> ::Result<()> {
+        if self.token.is_empty() && self.app.is_none() {
+            return Err(::WorkErr("Config needs a `token` or an `app` to authenticate with GitHub".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+/// Somewhere to read the structural data files from.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag="type")]
+pub enum DataSource {
+    /// A directory on the local file system.
+    #[serde(rename="local")]
+    Local { path: String },
+    /// The `data` directory of a GitHub repository, at a branch, tag, or commit
+    /// (the default branch if absent).
+    #[serde(rename="github")]
+    GitHub {
+        repository: String,
+        #[serde(default, rename="ref")]
+        git_ref: Option<String>,
+    },
+    /// A web server, the files are found relative to `url`.
+    #[serde(rename="url")]
+    Url { url: String },
+}
+
+impl fmt::Display for DataSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataSource::Local { ref path } => write!(f, "local:{}", path),
+            DataSource::GitHub { ref repository, git_ref: Some(ref r) } => write!(f, "github:{}@{}", repository, r),
+            DataSource::GitHub { ref repository, git_ref: None } => write!(f, "github:{}", repository),
+            DataSource::Url { ref url } => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Credentials for a GitHub App, used to authenticate with `api_base`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AppConfig {
+    pub app_id: u64,
+    pub installation_id: u64,
+    /// The app's private key, DER encoded. GitHub supplies the key as PEM, to
+    /// convert it use `openssl rsa -in key.pem -outform DER -out key.der`.
+    pub private_key_path: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Backend {
+    /// One REST request per tab-category pair.
+    #[default]
+    #[serde(rename="rest")]
+    Rest,
+    /// Batched GraphQL requests, falling back to REST if they fail.
+    #[serde(rename="graphql")]
+    Graphql,
+}
+
+fn default_api_base() -> String {
+    DEFAULT_API_BASE.to_owned()
 }
 
 /// Reads a config from CONFIG_PATH.
 pub fn read_config() -> ::Result<Config> {
     let file = File::open(CONFIG_PATH)?;
     let config: Config = serde_json::from_reader(file)?;
+    config.check()?;
     Ok(config)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::mock_config;
+
+    #[test]
+    fn test_data_sources() {
+        let mut config = mock_config();
+        assert_eq!(config.data_sources()[0].to_string(), "github:nrc/find-work");
+        config.dev_mode = true;
+        assert_eq!(config.data_sources()[0].to_string(), "local:../data");
+
+        config.data_sources = serde_json::from_str(r#"[
+            { "type": "github", "repository": "nrc/find-work", "ref": "staging" },
+            { "type": "url", "url": "https://example.com/find-work/data" }
+        ]"#).unwrap_or_else(|s| panic!("{:?}", s));
+        let sources: Vec<String> = config.data_sources().iter().map(|s| s.to_string()).collect();
+        assert_eq!(sources, vec!["github:nrc/find-work@staging", "https://example.com/find-work/data"]);
+    }
+
+    #[test]
+    fn test_check() {
+        let mut config = mock_config();
+        assert!(config.check().is_ok());
+        config.token = String::new();
+        assert!(config.check().is_err());
+        config.app = Some(AppConfig {
+            app_id: 1,
+            installation_id: 2,
+            private_key_path: "key.der".to_owned(),
+        });
+        assert!(config.check().is_ok());
+    }
+}
@@ -1,53 +1,298 @@
-use config::Config;
-use github::Client;
+use config::{Config, DataSource};
+use date;
+use github::{self, Client, ClientState, RepoDir};
+use gitlab;
 
-use std::collections::HashMap;
-use std::fs::File;
+use reqwest::{self, header, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde_json;
+use serde_yaml;
+use toml;
 
+// The directory of a GitHub repository containing the data.
 const DATA_ROOT: &'static str = "data";
-const TABS: &'static str = "tabs.json";
-const CATEGORIES: &'static str = "categories.json";
-const TAB_CATEGORY: &'static str = "tab-category.json";
+// Each data file may be in any one of these formats.
+const TABS: [&str; 3] = ["tabs.json", "tabs.toml", "tabs.yaml"];
+const CATEGORIES: [&str; 3] = ["categories.json", "categories.toml", "categories.yaml"];
+const TAB_CATEGORY: [&str; 3] = ["tab-category.json", "tab-category.toml", "tab-category.yaml"];
+// Tag definitions are optional.
+const TAGS: [&str; 3] = ["tags.json", "tags.toml", "tags.yaml"];
 
 
-// Data for structuring output
-pub fn fetch_structural_data(config: &Config) -> ::Result<StructuralData> {
-    if config.dev_mode {
-        make_structural_data(LocalFileLoader)
-    } else {
-        make_structural_data(Client::new(config)?)
+// Data for structuring output, from the first of the configured sources which
+// works.
+pub fn fetch_structural_data(config: &Config, client_state: &ClientState) -> ::Result<StructuralData> {
+    let mut errors = vec![];
+    for source in config.data_sources() {
+        match fetch_from_source(config, client_state, &source) {
+            Ok(mut data) => {
+                data.source = Some(source.to_string());
+                return Ok(data);
+            }
+            Err(e) => {
+                warn!("Could not load structural data from {}: {}", source, e.0);
+                errors.push(format!("{}: {}", source, e.0));
+            }
+        }
+    }
+    Err(::WorkErr(format!("Could not load structural data from any source\n{}", errors.join("\n"))))
+}
+
+fn fetch_from_source(config: &Config, client_state: &ClientState, source: &DataSource) -> ::Result<StructuralData> {
+    match *source {
+        DataSource::Local { ref path } => make_structural_data(LocalFileLoader::new(path)),
+        DataSource::GitHub { ref repository, ref git_ref } => {
+            let client = Client::new(config, client_state)?;
+            let mut loader = RepoDir::new(client, repository, DATA_ROOT, git_ref.as_ref().map(|r| &**r));
+            let sha = loader.pin_ref()?;
+            let mut data = make_structural_data(loader)?;
+            data.sha = Some(sha);
+            Ok(data)
+        }
+        DataSource::Url { ref url } => make_structural_data(HttpLoader::new(url)?),
     }
 }
 
 fn make_structural_data<F: FetchFile>(loader: F) -> ::Result<StructuralData> {
-    let tabs = loader.fetch_file(&format!("{}/{}", DATA_ROOT, TABS))?;
-    let categories = loader.fetch_file(&format!("{}/{}", DATA_ROOT, CATEGORIES))?;
-    let tab_category = loader.fetch_file(&format!("{}/{}", DATA_ROOT, TAB_CATEGORY))?;
-    
-    let data = StructuralData::from_raw_data(&tabs, &categories, &tab_category)?;
+    let data = match load_data(&loader) {
+        Ok(data) => data,
+        Err(diagnostics) => {
+            let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+            return Err(::WorkErr(messages.join("\n")));
+        }
+    };
+
+    for d in &data.diagnostics {
+        match d.level {
+            Level::Error => error!("{}", d),
+            Level::Warning => warn!("{}", d),
+        }
+    }
+    if data.has_errors() {
+        warn!("entries with errors were skipped, so some issues will be missing");
+    }
     Ok(data)
 }
 
-// Load the contents of a file from somewhere.
+/// Load the structural data from the directory `dir` and check it without
+/// using the network. Returns every problem found.
+pub fn check_local_data<P: AsRef<Path>>(dir: P) -> Vec<Diagnostic> {
+    match load_data(&LocalFileLoader::new(dir)) {
+        Ok(data) => data.diagnostics,
+        Err(diagnostics) => diagnostics,
+    }
+}
+
+// Find, read, parse, and validate the data files. If any file can't be read
+// or parsed, we can't validate, so we just return the problems.
+fn load_data<F: FetchFile>(loader: &F) -> ::std::result::Result<StructuralData, Vec<Diagnostic>> {
+    let files = loader.list_files().map_err(|e| vec![Diagnostic {
+        level: Level::Error,
+        file: "data",
+        index: None,
+        message: format!("could not list directory: {}", e.0),
+    }])?;
+
+    let names = (
+        find_file(&files, &TABS),
+        find_file(&files, &CATEGORIES),
+        find_file(&files, &TAB_CATEGORY),
+        find_optional_file(&files, &TAGS),
+    );
+    let names = match names {
+        (Ok(tabs), Ok(categories), Ok(tab_category), Ok(tags)) => FileNames { tabs, categories, tab_category, tags },
+        (tabs, categories, tab_category, tags) => {
+            return Err(vec![tabs.err(), categories.err(), tab_category.err(), tags.err()].into_iter().flatten().collect());
+        }
+    };
+
+    let entries = (
+        load_entries(loader, names.tabs),
+        load_entries(loader, names.categories),
+        load_entries(loader, names.tab_category),
+        names.tags.map_or(Ok(vec![]), |tags| load_entries(loader, tags)),
+    );
+    match entries {
+        (Ok(tabs), Ok(categories), Ok(tab_category), Ok(tags)) => {
+            Ok(StructuralData::validate(names, tabs, categories, tab_category, tags))
+        }
+        (tabs, categories, tab_category, tags) => {
+            Err(vec![tabs.err(), categories.err(), tab_category.err(), tags.err()].into_iter().flatten().collect())
+        }
+    }
+}
+
+// Like `find_file`, but it is fine if none of the files exist.
+fn find_optional_file(files: &[String], names: &[&'static str]) -> ::std::result::Result<Option<&'static str>, Diagnostic> {
+    if names.iter().any(|n| files.iter().any(|f| f == n)) {
+        find_file(files, names).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// Find the one file out of `names` (the same data in different formats) which
+// exists in `files`.
+fn find_file(files: &[String], names: &[&'static str]) -> ::std::result::Result<&'static str, Diagnostic> {
+    let found: Vec<&'static str> = names.iter().cloned().filter(|n| files.iter().any(|f| f == n)).collect();
+    match found.len() {
+        1 => Ok(found[0]),
+        0 => Err(Diagnostic {
+            level: Level::Error,
+            file: names[0],
+            index: None,
+            message: format!("file not found, expected one of {}", names.join(", ")),
+        }),
+        _ => Err(Diagnostic {
+            level: Level::Error,
+            file: found[0],
+            index: None,
+            message: format!("the same data is in more than one file ({}), remove all but one", found.join(", ")),
+        }),
+    }
+}
+
+// Read and parse one of the structural data files, according to its extension.
+fn load_entries<T, F>(loader: &F, file: &'static str) -> ::std::result::Result<Vec<T>, Diagnostic>
+where
+    T: ::serde::de::DeserializeOwned,
+    F: FetchFile,
+{
+    let error = |message| Diagnostic {
+        level: Level::Error,
+        file,
+        index: None,
+        message,
+    };
+
+    let contents = loader.fetch_file(file)
+        .map_err(|e| error(format!("could not read file: {}", e.0)))?;
+    // Parse errors include the line and column.
+    if let Some(key) = file.strip_suffix(".toml") {
+        // The top level of a TOML file must be a table, so entries are in an
+        // array of tables named for the file, e.g., `[[tabs]]`.
+        let mut table: HashMap<String, Vec<T>> = toml::from_str(&contents)
+            .map_err(|e| error(format!("could not parse file: {}", e)))?;
+        table.remove(key).ok_or_else(|| error(format!("expected entries in `[[{}]]`", key)))
+    } else if file.ends_with(".yaml") {
+        serde_yaml::from_str(&contents).map_err(|e| error(format!("could not parse file: {}", e)))
+    } else {
+        serde_json::from_str(&contents).map_err(|e| error(format!("could not parse file: {}", e)))
+    }
+}
+
+// Load the contents of a file from a data directory somewhere.
 pub trait FetchFile {
     fn fetch_file(&self, filename: &str) -> ::Result<String>;
+    // The names of the files in the data directory.
+    fn list_files(&self) -> ::Result<Vec<String>>;
+}
+
+struct LocalFileLoader {
+    dir: PathBuf,
 }
 
-struct LocalFileLoader;
+impl LocalFileLoader {
+    fn new<P: AsRef<Path>>(dir: P) -> LocalFileLoader {
+        LocalFileLoader { dir: dir.as_ref().to_owned() }
+    }
+}
 
 impl FetchFile for LocalFileLoader {
     fn fetch_file(&self, filename: &str) -> ::Result<String> {
-        let path = Path::new("..").to_owned();
-        let path = path.join(filename);
-        let mut file = File::open(path)?;
+        let mut file = File::open(self.dir.join(filename))?;
         let mut result = String::new();
         file.read_to_string(&mut result)?;
         Ok(result)
     }
+
+    fn list_files(&self) -> ::Result<Vec<String>> {
+        let mut result = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                result.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Loads files from a web server, relative to a base url.
+struct HttpLoader {
+    reqwest: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpLoader {
+    fn new(base_url: &str) -> ::Result<HttpLoader> {
+        Ok(HttpLoader {
+            reqwest: reqwest::Client::new()?,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        })
+    }
+}
+
+impl FetchFile for HttpLoader {
+    fn fetch_file(&self, filename: &str) -> ::Result<String> {
+        let url = format!("{}/{}", self.base_url, filename);
+        let req = self.reqwest.get(&url)?.header(header::UserAgent::new("nrc")).build();
+        debug!("request: `{:?}`", req);
+
+        let mut res = self.reqwest.execute(req)?;
+        debug!("response: `{:?}`", res);
+        if !res.status().is_success() {
+            return Err(::WorkErr(format!("Could not fetch {}: {:?}", url, res.status())));
+        }
+
+        let mut result = String::new();
+        res.read_to_string(&mut result)?;
+        Ok(result)
+    }
+
+    // There's no way to list a directory over HTTP, so we check for each of
+    // the files we know about.
+    fn list_files(&self) -> ::Result<Vec<String>> {
+        let mut result = vec![];
+        for &name in TABS.iter().chain(&CATEGORIES).chain(&TAB_CATEGORY).chain(&TAGS) {
+            let url = format!("{}/{}", self.base_url, name);
+            let req = self.reqwest.head(&url)?.header(header::UserAgent::new("nrc")).build();
+            let res = self.reqwest.execute(req)?;
+            debug!("response: `{:?}`", res);
+            if res.status().is_success() {
+                result.push(name.to_owned());
+            } else if res.status() != StatusCode::NotFound {
+                return Err(::WorkErr(format!("Could not fetch {}: {:?}", url, res.status())));
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// The names of the files the structural data was loaded from.
+#[derive(Clone, Copy, Debug)]
+pub struct FileNames {
+    pub tabs: &'static str,
+    pub categories: &'static str,
+    pub tab_category: &'static str,
+    /// `None` if there are no tag definitions.
+    pub tags: Option<&'static str>,
+}
+
+impl Default for FileNames {
+    fn default() -> FileNames {
+        FileNames {
+            tabs: TABS[0],
+            categories: CATEGORIES[0],
+            tab_category: TAB_CATEGORY[0],
+            tags: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -55,24 +300,201 @@ pub struct StructuralData {
     pub tabs: Vec<Tab>,
     pub categories: HashMap<String, Category>,
     pub tab_category: HashMap<String, Vec<TabCategory>>,
+    /// Tag definitions, in the order they were given.
+    pub tags: Vec<Tag>,
+    /// Problems found when validating the data. Entries with errors are not
+    /// included in the data.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The commit the data was read from, if it came from GitHub.
+    pub sha: Option<String>,
+    /// Where the data was read from, e.g., "github:nrc/find-work".
+    pub source: Option<String>,
 }
 
 impl StructuralData {
-    fn from_raw_data(tabs: &str, categories: &str, tab_category: &str) -> ::Result<StructuralData> {
-        let tabs: Vec<Tab> = serde_json::from_str(tabs)?;
-        let categories: Vec<Category> = serde_json::from_str(categories)?;
-        let tab_category: Vec<TabCategory> = serde_json::from_str(tab_category)?;
-
+    /// Make structural data from lists of entries, checking for dangling
+    /// references, duplicate ids, and so forth. Entries with errors are
+    /// skipped, all problems are recorded in `diagnostics`.
+    pub fn validate(
+        names: FileNames,
+        tabs: Vec<Tab>,
+        categories: Vec<Category>,
+        tab_category: Vec<TabCategory>,
+        tags: Vec<Tag>,
+    ) -> StructuralData {
         let mut result = StructuralData::default();
-        result.tabs = tabs;
-        for c in categories {
+
+        if let Some(file) = names.tags {
+            let mut tag_ids = HashSet::new();
+            for (i, t) in tags.into_iter().enumerate() {
+                if t.id.is_empty() {
+                    result.error(file, i, "tag has no id".to_owned());
+                    continue;
+                }
+                if !tag_ids.insert(t.id.clone()) {
+                    result.error(file, i, format!("duplicate tag id `{}`", t.id));
+                    continue;
+                }
+                if t.name.is_empty() {
+                    result.warning(file, i, format!("tag `{}` has no name", t.id));
+                }
+                result.tags.push(t);
+            }
+        }
+
+        let mut tab_ids = HashSet::new();
+        for (i, t) in tabs.into_iter().enumerate() {
+            if t.id.is_empty() {
+                result.error(names.tabs, i, "tab has no id".to_owned());
+                continue;
+            }
+            if tab_ids.contains(&t.id) {
+                result.error(names.tabs, i, format!("duplicate tab id `{}`", t.id));
+                continue;
+            }
+            if t.title.is_empty() {
+                result.warning(names.tabs, i, format!("tab `{}` has no title", t.id));
+            }
+            if let Some(bad) = t.active_from.iter().chain(t.active_until.iter()).find(|d| !date::is_valid(d)) {
+                result.error(names.tabs, i, format!(
+                    "tab `{}` has a bad date `{}`, expected `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SSZ`",
+                    t.id,
+                    bad,
+                ));
+                continue;
+            }
+            if let (Some(from), Some(until)) = (&t.active_from, &t.active_until) {
+                if from > until {
+                    result.warning(names.tabs, i, format!("tab `{}` is never active, `active_from` is after `active_until`", t.id));
+                }
+            }
+            // Only tabs we keep can be referred to by tab-categories.
+            tab_ids.insert(t.id.clone());
+            result.tabs.push(t);
+        }
+
+        for (i, c) in categories.into_iter().enumerate() {
+            if c.id.is_empty() {
+                result.error(names.categories, i, "category has no id".to_owned());
+                continue;
+            }
+            if result.categories.contains_key(&c.id) {
+                result.error(names.categories, i, format!("duplicate category id `{}`", c.id));
+                continue;
+            }
+            if c.repositories().is_empty() {
+                result.error(names.categories, i, format!("category `{}` has no repository", c.id));
+                continue;
+            }
+            if let Some(r) = c.repositories().into_iter().find(|r| r.split('/').filter(|s| !s.is_empty()).count() < 2) {
+                result.error(names.categories, i, format!("category `{}` has a bad repository `{}`, expected `owner/name` or `owner/*`", c.id, r));
+                continue;
+            }
+            if c.labels.iter().any(|l| l.is_empty()) {
+                result.warning(names.categories, i, format!("category `{}` has an empty label", c.id));
+            }
+            for l in &c.links {
+                if l.url.is_empty() {
+                    result.warning(names.categories, i, format!("category `{}` has a link with no url", c.id));
+                }
+            }
+            // Tags only need defining if there is a tags file.
+            if names.tags.is_some() {
+                for tag in &c.tags {
+                    if !result.tags.iter().any(|t| &t.id == tag) {
+                        result.warning(names.categories, i, format!("category `{}` has an undefined tag `{}`", c.id, tag));
+                    }
+                }
+            }
             result.categories.insert(c.id.clone(), c);
         }
-        for tc in tab_category {
-            result.tab_category.entry(tc.tab.clone()).or_insert(vec![]).push(tc.clone());
+
+        let mut pairs = HashSet::new();
+        for (i, tc) in tab_category.into_iter().enumerate() {
+            if !tab_ids.contains(&tc.tab) {
+                result.error(names.tab_category, i, format!("unknown tab `{}`", tc.tab));
+                continue;
+            }
+            let no_labels = match result.categories.get(&tc.category) {
+                Some(c) => c.labels.is_empty() && tc.labels.is_empty() && c.any_labels.is_empty() && tc.any_labels.is_empty(),
+                None => {
+                    result.error(names.tab_category, i, format!("unknown category `{}`", tc.category));
+                    continue;
+                }
+            };
+            if !pairs.insert((tc.tab.clone(), tc.category.clone())) {
+                result.error(names.tab_category, i, format!("duplicate entry for tab `{}` and category `{}`", tc.tab, tc.category));
+                continue;
+            }
+            if no_labels && tc.milestone.is_none() {
+                result.warning(names.tab_category, i, format!(
+                    "no labels or milestone for tab `{}` and category `{}`, every open issue will be listed",
+                    tc.tab,
+                    tc.category,
+                ));
+            }
+            if tc.labels.iter().any(|l| l.is_empty()) {
+                result.warning(names.tab_category, i, format!("empty label for tab `{}` and category `{}`", tc.tab, tc.category));
+            }
+            result.tab_category.entry(tc.tab.clone()).or_insert(vec![]).push(tc);
         }
 
-        Ok(result)
+        let mut unused: Vec<&String> = result.categories.keys().filter(|id| !pairs.iter().any(|p| &p.1 == *id)).collect();
+        unused.sort();
+        let unused: Vec<Diagnostic> = unused.into_iter().map(|id| Diagnostic {
+            level: Level::Warning,
+            file: names.categories,
+            index: None,
+            message: format!("category `{}` is not used by any tab", id),
+        }).collect();
+        result.diagnostics.extend(unused);
+
+        result
+    }
+
+    /// True if any errors were found by validation.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.level == Level::Error)
+    }
+
+    fn error(&mut self, file: &'static str, index: usize, message: String) {
+        self.diagnostics.push(Diagnostic { level: Level::Error, file, index: Some(index), message });
+    }
+
+    fn warning(&mut self, file: &'static str, index: usize, message: String) {
+        self.diagnostics.push(Diagnostic { level: Level::Warning, file, index: Some(index), message });
+    }
+}
+
+/// A problem found when validating structural data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// The data file containing the problem.
+    pub file: &'static str,
+    /// The position of the problem entry in the file, counting from zero.
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    /// The entry is skipped.
+    Error,
+    /// The entry is used, but is probably wrong.
+    Warning,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        match self.index {
+            Some(index) => write!(f, "{}: {}, entry {}: {}", level, self.file, index, self.message),
+            None => write!(f, "{}: {}: {}", level, self.file, self.message),
+        }
     }
 }
 
@@ -81,6 +503,35 @@ pub struct Tab {
     pub id: String,
     pub title: String,
     pub description: String,
+    /// The tab is only shown from this date or time.
+    #[serde(default)]
+    pub active_from: Option<String>,
+    /// The tab is only shown until this date or time.
+    #[serde(default)]
+    pub active_until: Option<String>,
+    /// Hidden tabs are not listed, but can still be viewed by id.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Tabs are listed in ascending order, then in the order they were given.
+    #[serde(default)]
+    pub order: i32,
+}
+
+impl Tab {
+    /// Whether the tab should be shown at the time `now`.
+    pub fn is_active(&self, now: &str) -> bool {
+        date::is_between(now, self.active_from.as_deref(), self.active_until.as_deref())
+    }
+}
+
+/// A tag which categories may have, used for filtering.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Tag {
+    pub id: String,
+    /// Shown to the user in place of the id.
+    pub name: String,
+    /// Tags in the same group are alternatives, e.g., "language" or "kind".
+    pub group: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -88,12 +539,79 @@ pub struct Category {
     pub id: String,
     pub title: String,
     pub description: String,
+    #[serde(default)]
     pub repository: String,
+    /// More repositories, in addition to `repository`.
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub host: Host,
+    #[serde(default)]
+    pub api_base: Option<String>,
     pub labels: Vec<String>,
+    #[serde(default)]
+    pub any_labels: Vec<String>,
+    #[serde(default)]
+    pub exclude_labels: Vec<String>,
     pub links: Vec<Link>,
     pub tags: Vec<String>,
 }
 
+impl Category {
+    /// The base url of the API which hosts this category's repository.
+    pub fn api_base<'a>(&'a self, config: &'a Config) -> &'a str {
+        match self.api_base {
+            Some(ref api_base) => api_base,
+            None => match self.host {
+                Host::GitHub => &config.api_base,
+                Host::GitLab => gitlab::DEFAULT_API_BASE,
+            },
+        }
+    }
+
+    /// All of this category's repositories, "owner/name" or "owner/*" for
+    /// every (unarchived) repository belonging to owner.
+    pub fn repositories(&self) -> Vec<&str> {
+        Some(&self.repository)
+            .into_iter()
+            .filter(|r| !r.is_empty())
+            .chain(self.repositories.iter())
+            .map(|r| &**r)
+            .collect()
+    }
+
+    /// Links to the web pages of the category's repositories. An `owner/*`
+    /// wildcard links to the owner's page.
+    pub fn repository_links(&self, config: &Config) -> Vec<Link> {
+        let web_base = match self.host {
+            Host::GitHub => github::web_base(self.api_base(config)),
+            Host::GitLab => gitlab::web_base(self.api_base(config)),
+        };
+        let repositories = self.repositories();
+        let single = repositories.len() == 1;
+        repositories
+            .into_iter()
+            .map(|r| {
+                let path = r.trim_end_matches("/*");
+                Link {
+                    text: if single && path == r { "repository".to_owned() } else { path.to_owned() },
+                    url: format!("{}/{}", web_base, path),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Where a category's issues are hosted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Hash)]
+pub enum Host {
+    #[default]
+    #[serde(rename="github")]
+    GitHub,
+    #[serde(rename="gitlab")]
+    GitLab,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Link {
     pub text: String,
@@ -105,27 +623,304 @@ pub struct TabCategory {
     pub tab: String,
     pub category: String,
     pub labels: Vec<String>,
+    #[serde(default)]
+    pub any_labels: Vec<String>,
+    #[serde(default)]
+    pub exclude_labels: Vec<String>,
     pub milestone: Option<String>,
     pub link: Option<Link>,
+    #[serde(default)]
+    pub max_issues: Option<usize>,
+    #[serde(default)]
+    pub include_pull_requests: bool,
+    #[serde(default)]
+    pub hide_assigned: Option<bool>,
+}
+
+impl TabCategory {
+    /// Whether issues which already have an assignee should be left out.
+    pub fn hide_assigned(&self, config: &Config) -> bool {
+        self.hide_assigned.unwrap_or(config.hide_assigned)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use mock::mock_config;
+    use config;
+    use mock::{mock_config, mock_struct_data};
 
     #[test]
     fn test_fetch_structural_data() {
-        let data = fetch_structural_data(&mock_config()).unwrap();
+        let data = fetch_structural_data(&mock_config(), &ClientState::default()).unwrap();
         assert!(data.tabs.iter().any(|t| t.id == "starters"));
         assert!(data.categories.contains_key("rustfmt"));
         assert!(data.tab_category.contains_key("starters"));
+        assert_eq!(data.source, Some("github:nrc/find-work".to_owned()));
+    }
+
+    #[test]
+    fn test_fetch_from_sources() {
+        let mut config = mock_config();
+        config.data_sources = vec![
+            DataSource::Local { path: "does-not-exist".to_owned() },
+            DataSource::Local { path: config::LOCAL_DATA_PATH.to_owned() },
+        ];
+        let data = fetch_structural_data(&config, &ClientState::default()).unwrap_or_else(|s| panic!("{:?}", s));
+        assert!(data.tabs.iter().any(|t| t.id == "starters"));
+        assert_eq!(data.source, Some("local:../data".to_owned()));
+        assert_eq!(data.sha, None);
+
+        config.data_sources.remove(1);
+        assert!(fetch_structural_data(&config, &ClientState::default()).is_err());
+    }
+
+    fn tab(id: &str) -> Tab {
+        Tab {
+            id: id.to_owned(),
+            title: id.to_owned(),
+            description: String::new(),
+            active_from: None,
+            active_until: None,
+            hidden: false,
+            order: 0,
+        }
+    }
+
+    fn tab_category(tab: &str, category: &str) -> TabCategory {
+        let mut result = mock_struct_data().tab_category["foo"][0].clone();
+        result.tab = tab.to_owned();
+        result.category = category.to_owned();
+        result
+    }
+
+    #[test]
+    fn test_validate() {
+        let rustfmt = mock_struct_data().categories["rustfmt"].clone();
+        let data = StructuralData::validate(
+            FileNames::default(),
+            vec![tab("foo"), tab("bar"), tab("foo")],
+            vec![rustfmt.clone(), rustfmt],
+            vec![
+                tab_category("foo", "rustfmt"),
+                tab_category("baz", "rustfmt"),
+                tab_category("foo", "rls"),
+                tab_category("foo", "rustfmt"),
+            ],
+            vec![],
+        );
+
+        assert_eq!(data.tabs.len(), 2);
+        assert_eq!(data.categories.len(), 1);
+        assert_eq!(data.tab_category.len(), 1);
+        assert_eq!(data.tab_category["foo"].len(), 1);
+        assert!(data.has_errors());
+
+        let errors: Vec<String> = data.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(errors, vec![
+            "error: tabs.json, entry 2: duplicate tab id `foo`".to_owned(),
+            "error: categories.json, entry 1: duplicate category id `rustfmt`".to_owned(),
+            "error: tab-category.json, entry 1: unknown tab `baz`".to_owned(),
+            "error: tab-category.json, entry 2: unknown category `rls`".to_owned(),
+            "error: tab-category.json, entry 3: duplicate entry for tab `foo` and category `rustfmt`".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn test_validate_warnings() {
+        let mut category = mock_struct_data().categories["rustfmt"].clone();
+        category.labels = vec![];
+        let mut unused = category.clone();
+        unused.id = "unused".to_owned();
+        let mut tc = tab_category("foo", "rustfmt");
+        tc.labels = vec![];
+
+        let data = StructuralData::validate(FileNames::default(), vec![tab("foo")], vec![category.clone(), unused.clone()], vec![tc.clone()], vec![]);
+        assert!(!data.has_errors());
+        assert_eq!(data.tab_category["foo"].len(), 1);
+        assert_eq!(data.diagnostics.len(), 2);
+        assert!(data.diagnostics.iter().all(|d| d.level == Level::Warning));
+
+        // 'Any of' labels are labels too.
+        tc.any_labels = vec!["E-easy".to_owned()];
+        let data = StructuralData::validate(FileNames::default(), vec![tab("foo")], vec![category, unused], vec![tc], vec![]);
+        let messages: Vec<String> = data.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("unused"));
+    }
+
+    #[test]
+    fn test_validate_tab_dates() {
+        let mut bad = tab("foo");
+        bad.active_until = Some("18/12/2017".to_owned());
+        let mut never = tab("bar");
+        never.active_from = Some("2017-12-18".to_owned());
+        never.active_until = Some("2017-09-18".to_owned());
+
+        let rustfmt = mock_struct_data().categories["rustfmt"].clone();
+        let data = StructuralData::validate(
+            FileNames::default(),
+            vec![bad, never],
+            vec![rustfmt],
+            vec![tab_category("foo", "rustfmt"), tab_category("bar", "rustfmt")],
+            vec![],
+        );
+        assert_eq!(data.tabs.len(), 1);
+        // The tab with a bad date was skipped, so it can't be used.
+        assert!(!data.tab_category.contains_key("foo"));
+        assert_eq!(data.tab_category["bar"].len(), 1);
+        let messages: Vec<String> = data.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec![
+            "error: tabs.json, entry 0: tab `foo` has a bad date `18/12/2017`, expected `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SSZ`".to_owned(),
+            "warning: tabs.json, entry 1: tab `bar` is never active, `active_from` is after `active_until`".to_owned(),
+            "error: tab-category.json, entry 0: unknown tab `foo`".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn test_repositories() {
+        let config = mock_config();
+        let mut category = mock_struct_data().categories["rustfmt"].clone();
+        let links = category.repository_links(&config);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].text, "repository");
+        assert_eq!(links[0].url, "https://github.com/rust-lang-nursery/rustfmt");
+
+        category.repositories = vec!["tokio-rs/*".to_owned()];
+        assert_eq!(category.repositories(), vec!["rust-lang-nursery/rustfmt", "tokio-rs/*"]);
+        let links = category.repository_links(&config);
+        assert_eq!(links[0].text, "rust-lang-nursery/rustfmt");
+        assert_eq!(links[1].text, "tokio-rs");
+        assert_eq!(links[1].url, "https://github.com/tokio-rs");
+
+        category.repository = String::new();
+        assert_eq!(category.repositories(), vec!["tokio-rs/*"]);
+        let data = StructuralData::validate(FileNames::default(), vec![tab("foo")], vec![category.clone()], vec![tab_category("foo", "rustfmt")], vec![]);
+        assert!(!data.has_errors());
+
+        category.repositories = vec![];
+        let data = StructuralData::validate(FileNames::default(), vec![tab("foo")], vec![category], vec![tab_category("foo", "rustfmt")], vec![]);
+        assert_eq!(data.diagnostics[0].to_string(), "error: categories.json, entry 0: category `rustfmt` has no repository");
+    }
+
+    struct MockLoader(HashMap<String, String>);
+
+    impl FetchFile for MockLoader {
+        fn fetch_file(&self, filename: &str) -> ::Result<String> {
+            self.0.get(filename).cloned().ok_or_else(|| ::WorkErr(format!("not found: {}", filename)))
+        }
+
+        fn list_files(&self) -> ::Result<Vec<String>> {
+            Ok(self.0.keys().cloned().collect())
+        }
+    }
+
+    fn mock_loader(files: &[(&str, &str)]) -> MockLoader {
+        MockLoader(files.iter().map(|&(name, contents)| (name.to_owned(), contents.to_owned())).collect())
+    }
+
+    const TABS_TOML: &str = r#"
+[[tabs]]
+id = "foo"
+title = "Foo"
+description = """
+A long description,
+over several lines.
+"""
+"#;
+
+    const CATEGORIES_YAML: &str = r#"
+- id: rustfmt
+  title: Rustfmt
+  description: A tool for formatting Rust code
+  repository: rust-lang-nursery/rustfmt
+  labels: []
+  links: []
+  tags: [tool]
+"#;
+
+    const TAB_CATEGORY_JSON: &str = r#"[{
+    "tab": "foo",
+    "category": "rustfmt",
+    "labels": ["good first issue"],
+    "milestone": null,
+    "link": null
+}]"#;
+
+    #[test]
+    fn test_load_formats() {
+        let loader = mock_loader(&[
+            ("tabs.toml", TABS_TOML),
+            ("categories.yaml", CATEGORIES_YAML),
+            ("tab-category.json", TAB_CATEGORY_JSON),
+        ]);
+        let data = load_data(&loader).unwrap_or_else(|s| panic!("{:?}", s));
+        assert!(data.diagnostics.is_empty(), "{:?}", data.diagnostics);
+        assert_eq!(data.tabs[0].description, "A long description,\nover several lines.\n");
+        assert_eq!(data.categories["rustfmt"].tags, vec!["tool".to_owned()]);
+        assert_eq!(data.tab_category["foo"][0].labels, vec!["good first issue".to_owned()]);
+    }
+
+    #[test]
+    fn test_load_duplicate_formats() {
+        let loader = mock_loader(&[
+            ("tabs.toml", TABS_TOML),
+            ("tabs.json", "[]"),
+            ("categories.yaml", CATEGORIES_YAML),
+        ]);
+        let diagnostics = load_data(&loader).unwrap_err();
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec![
+            "error: tabs.json: the same data is in more than one file (tabs.json, tabs.toml), remove all but one".to_owned(),
+            "error: tab-category.json: file not found, expected one of tab-category.json, tab-category.toml, tab-category.yaml".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn test_load_tags() {
+        let tags = r#"[
+            { "id": "tool", "name": "Tool", "group": "kind" },
+            { "id": "tool", "name": "Tool", "group": "kind" }
+        ]"#;
+        let loader = mock_loader(&[
+            ("tabs.toml", TABS_TOML),
+            ("categories.yaml", CATEGORIES_YAML),
+            ("tab-category.json", TAB_CATEGORY_JSON),
+            ("tags.json", tags),
+        ]);
+        let data = load_data(&loader).unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(data.tags.len(), 1);
+        assert_eq!(data.tags[0].group, "kind");
+        let messages: Vec<String> = data.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec!["error: tags.json, entry 1: duplicate tag id `tool`".to_owned()]);
+
+        let tags = r#"[{ "id": "lang-rust", "name": "Rust", "group": "language" }]"#;
+        let loader = mock_loader(&[
+            ("tabs.toml", TABS_TOML),
+            ("categories.yaml", CATEGORIES_YAML),
+            ("tab-category.json", TAB_CATEGORY_JSON),
+            ("tags.json", tags),
+        ]);
+        let data = load_data(&loader).unwrap_or_else(|s| panic!("{:?}", s));
+        let messages: Vec<String> = data.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, vec!["warning: categories.yaml, entry 0: category `rustfmt` has an undefined tag `tool`".to_owned()]);
+    }
+
+    #[test]
+    fn test_check_local_data() {
+        let diagnostics = check_local_data(config::LOCAL_DATA_PATH);
+        assert!(diagnostics.iter().all(|d| d.level != Level::Error), "{:?}", diagnostics);
+
+        let diagnostics = check_local_data("no-such-dir");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, Level::Error);
     }
 
     #[test]
     fn test_local_file_loader() {
-        let loader = LocalFileLoader;
-        let s = loader.fetch_file("back/test-token.txt.example").unwrap();
+        let loader = LocalFileLoader::new(".");
+        let s = loader.fetch_file("test-token.txt.example").unwrap();
         assert_eq!(s, "Put your GitHub token here\n");
+        assert!(loader.list_files().unwrap().contains(&"Cargo.toml".to_owned()));
     }
 }
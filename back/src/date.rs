@@ -0,0 +1,91 @@
+// Dates and times as ISO 8601 strings in UTC, e.g., "2017-10-02T19:15:59Z", the
+// format GitHub uses. Strings in this format sort chronologically, so we
+// compare them as strings rather than parsing them.
+
+use std::cmp;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The current time.
+pub fn now() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    from_unix(secs)
+}
+
+/// Format a time given in seconds since the Unix epoch.
+pub fn from_unix(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    )
+}
+
+// The year, month, and day of a number of days since the Unix epoch, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+/// Whether `s` is a date, e.g., "2017-10-02", or a time, e.g.,
+/// "2017-10-02T19:15:59Z".
+pub fn is_valid(s: &str) -> bool {
+    let pattern = if s.len() == 10 { "dddd-dd-dd" } else { "dddd-dd-ddTdd:dd:ddZ" };
+    s.len() == pattern.len()
+        && s.chars().zip(pattern.chars()).all(|(c, p)| if p == 'd' { c.is_ascii_digit() } else { c == p })
+}
+
+/// Whether the time `now` is between `from` and `until`, inclusive, where
+/// either may be missing. A date without a time covers the whole day.
+pub fn is_between(now: &str, from: Option<&str>, until: Option<&str>) -> bool {
+    // Compare only as much of `now` as is given in each bound.
+    let truncate = |bound: &str| &now[..cmp::min(bound.len(), now.len())];
+    from.is_none_or(|from| truncate(from) >= from) && until.is_none_or(|until| truncate(until) <= until)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(from_unix(0), "1970-01-01T00:00:00Z");
+        assert_eq!(from_unix(1506971759), "2017-10-02T19:15:59Z");
+        assert_eq!(from_unix(951782400), "2000-02-29T00:00:00Z");
+        assert!(is_valid(&now()));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("2017-10-02"));
+        assert!(is_valid("2017-10-02T19:15:59Z"));
+        assert!(!is_valid("2017-10-2"));
+        assert!(!is_valid("2017-10-02 19:15:59"));
+        assert!(!is_valid("October 2nd"));
+    }
+
+    #[test]
+    fn test_is_between() {
+        let now = "2017-10-02T19:15:59Z";
+        assert!(is_between(now, None, None));
+        assert!(is_between(now, Some("2017-10-02"), Some("2017-10-02")));
+        assert!(is_between(now, Some("2017-09-18"), None));
+        assert!(!is_between(now, Some("2017-10-03"), None));
+        assert!(!is_between(now, None, Some("2017-10-01")));
+        assert!(!is_between(now, None, Some("2017-10-02T12:00:00Z")));
+    }
+}
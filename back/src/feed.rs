@@ -0,0 +1,234 @@
+// Atom feeds of the issues in a tab or category, newest first.
+//
+// GitHub doesn't tell us when an issue started matching a category (e.g., when
+// a label was added), so we remember when we first saw each issue instead.
+
+use blob::{Blob, Tab};
+use config::Config;
+use github::Issue;
+
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// When each issue in the data was first seen, keyed by issue url.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FirstSeen {
+    times: HashMap<String, String>,
+    // When the data was last recorded.
+    updated: String,
+}
+
+impl FirstSeen {
+    /// Record the issues in `blob` as seen at `now`. Issues which are no
+    /// longer in the data are forgotten.
+    pub fn record(&mut self, blob: &Blob, now: &str) {
+        let mut times = HashMap::new();
+        for tab in blob.tabs.iter().chain(&blob.hidden_tabs) {
+            for category in &tab.categories {
+                for issue in &category.issues {
+                    let time = self.times.get(&issue.url).map_or(now, |t| &**t);
+                    times.insert(issue.url.clone(), time.to_owned());
+                }
+            }
+        }
+        self.times = times;
+        self.updated = now.to_owned();
+    }
+
+    fn get(&self, issue: &Issue) -> &str {
+        self.times.get(&issue.url).unwrap_or(&self.updated)
+    }
+
+    /// The times saved by a previous run, if the config has a `cache_path`
+    /// and they were saved.
+    pub fn load(config: &Config) -> FirstSeen {
+        if let Some(ref cache_path) = config.cache_path {
+            let path = save_path(cache_path);
+            match read_first_seen(&path) {
+                Ok(first_seen) => return first_seen,
+                Err(e) => info!("Could not read first seen times `{}`: {}", path.display(), e.0),
+            }
+        }
+        FirstSeen::default()
+    }
+
+    /// Write the times to disk next to the response cache, if the config has
+    /// a `cache_path`.
+    pub fn save(&self, config: &Config) -> ::Result<()> {
+        if let Some(ref cache_path) = config.cache_path {
+            let file = fs::File::create(save_path(cache_path))?;
+            serde_json::to_writer(file, self)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_first_seen(path: &Path) -> ::Result<FirstSeen> {
+    let file = fs::File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+/// Where first seen times are saved, given the config's `cache_path`, e.g.,
+/// `data/response-cache-first-seen.json` for `data/response-cache.json`.
+pub fn save_path(cache_path: &str) -> PathBuf {
+    let cache_path = Path::new(cache_path);
+    let stem = cache_path.file_stem().map_or("cache".into(), |s| s.to_string_lossy());
+    cache_path.with_file_name(format!("{}-first-seen.json", stem))
+}
+
+/// The feed for a tab, or for a category in it if `category` is given.
+/// Returns `None` if there is no such tab or category.
+pub fn make_feed(blob: &Blob, first_seen: &FirstSeen, tab: &str, category: Option<&str>) -> Option<String> {
+    let tab = blob.tabs.iter().chain(&blob.hidden_tabs).find(|t| t.id == tab)?;
+    let (id, title, categories) = match category {
+        Some(category) => {
+            let category = tab.categories.iter().find(|c| c.id == category)?;
+            (
+                format!("{}/{}", tab.id, category.id),
+                format!("{}: {}", tab.title, category.title),
+                vec![category],
+            )
+        }
+        None => (tab.id.clone(), tab.title.clone(), tab.categories.iter().collect()),
+    };
+
+    // An issue may be in more than one category, it only gets one entry.
+    let mut urls = HashSet::new();
+    let mut entries: Vec<(&Issue, &str)> = categories
+        .iter()
+        .flat_map(|c| c.issues.iter().map(move |i| (i, &*c.title)))
+        .filter(|&(i, _)| urls.insert(&i.url))
+        .collect();
+    entries.sort_by(|a, b| {
+        first_seen.get(b.0).cmp(first_seen.get(a.0)).then(b.0.created_at.cmp(&a.0.created_at))
+    });
+
+    Some(write_feed(tab, &id, &title, &entries, first_seen))
+}
+
+fn write_feed(tab: &Tab, id: &str, title: &str, entries: &[(&Issue, &str)], first_seen: &FirstSeen) -> String {
+    let updated = entries.first().map_or(&*first_seen.updated, |e| first_seen.get(e.0));
+
+    let mut result = String::new();
+    result.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    result.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    writeln!(result, "  <id>tag:find-work,2017:feed/{}</id>", escape(id)).unwrap();
+    writeln!(result, "  <title>{} - find work</title>", escape(title)).unwrap();
+    writeln!(result, "  <subtitle>{}</subtitle>", escape(&tab.description)).unwrap();
+    writeln!(result, "  <updated>{}</updated>", updated).unwrap();
+    result.push_str("  <author><name>find work</name></author>\n");
+    for &(issue, category) in entries {
+        result.push_str("  <entry>\n");
+        // Issue urls never change, so make good ids.
+        writeln!(result, "    <id>{}</id>", escape(&issue.url)).unwrap();
+        writeln!(result, "    <title>{}#{}: {}</title>", escape(&issue.repository), issue.number, escape(&issue.title)).unwrap();
+        writeln!(result, "    <link href=\"{}\"/>", escape(&issue.url)).unwrap();
+        writeln!(result, "    <updated>{}</updated>", first_seen.get(issue)).unwrap();
+        writeln!(result, "    <published>{}</published>", issue.created_at).unwrap();
+        writeln!(result, "    <author><name>{}</name><uri>{}</uri></author>", escape(&issue.author.login), escape(&issue.author.url)).unwrap();
+        writeln!(result, "    <category term=\"{}\"/>", escape(category)).unwrap();
+        for label in &issue.labels {
+            writeln!(result, "    <category term=\"{}\"/>", escape(&label.name)).unwrap();
+        }
+        writeln!(result, "    <content type=\"text\">{}</content>", escape(&issue.body)).unwrap();
+        result.push_str("  </entry>\n");
+    }
+    result.push_str("</feed>\n");
+    result
+}
+
+// Escape text for use in XML content or attribute values.
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            // Control characters other than whitespace aren't allowed in XML.
+            c if c.is_control() && c != '\n' && c != '\r' && c != '\t' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::{mock_config, mock_issue_data, mock_struct_data};
+
+    fn make_blob(bar_url: &str) -> Blob {
+        let mut issues = mock_issue_data();
+        issues.issues.get_mut(&("foo".to_owned(), "rustfmt".to_owned())).unwrap()[0].url = "https://github.com/rust-lang-nursery/rustfmt/issues/42".to_owned();
+        issues.issues.get_mut(&("bar".to_owned(), "rustfmt".to_owned())).unwrap()[0].url = bar_url.to_owned();
+        Blob::make(&mock_config(), &mock_struct_data(), &issues).unwrap_or_else(|s| panic!("{:?}", s))
+    }
+
+    #[test]
+    fn test_first_seen() {
+        let mut first_seen = FirstSeen::default();
+        let blob = make_blob("https://github.com/rust-lang-nursery/rustfmt/issues/43");
+        first_seen.record(&blob, "2017-10-02T00:00:00Z");
+        first_seen.record(&blob, "2017-10-03T00:00:00Z");
+        let issue = &blob.tabs[0].categories[0].issues[0];
+        assert_eq!(first_seen.get(issue), "2017-10-02T00:00:00Z");
+
+        // Issue 43 is replaced by issue 44.
+        let blob = make_blob("https://github.com/rust-lang-nursery/rustfmt/issues/44");
+        first_seen.record(&blob, "2017-10-04T00:00:00Z");
+        assert_eq!(first_seen.get(issue), "2017-10-02T00:00:00Z");
+        assert_eq!(first_seen.times.len(), 2);
+        assert_eq!(first_seen.times["https://github.com/rust-lang-nursery/rustfmt/issues/44"], "2017-10-04T00:00:00Z");
+    }
+
+    #[test]
+    fn test_save_first_seen() {
+        assert_eq!(save_path("../data/response-cache.json"), Path::new("../data/response-cache-first-seen.json"));
+
+        let mut config = mock_config();
+        // Unique to this test and process, so that test runs don't interfere.
+        let cache_path = ::std::env::temp_dir().join(format!("find-work-test-save-first-seen-{}.json", ::std::process::id()));
+        config.cache_path = Some(cache_path.to_str().unwrap().to_owned());
+        assert!(FirstSeen::load(&config).times.is_empty());
+
+        let mut first_seen = FirstSeen::default();
+        first_seen.record(&make_blob("https://github.com/rust-lang-nursery/rustfmt/issues/43"), "2017-10-02T00:00:00Z");
+        first_seen.save(&config).unwrap_or_else(|s| panic!("{:?}", s));
+
+        let loaded = FirstSeen::load(&config);
+        fs::remove_file(save_path(config.cache_path.as_ref().unwrap())).unwrap();
+        assert_eq!(loaded.times, first_seen.times);
+        assert_eq!(loaded.updated, "2017-10-02T00:00:00Z");
+    }
+
+    #[test]
+    fn test_make_feed() {
+        let mut first_seen = FirstSeen::default();
+        let mut blob = make_blob("https://github.com/rust-lang-nursery/rustfmt/issues/43");
+        blob.tabs[0].categories[0].issues[0].title = "Crash on <T> & \"U\"".to_owned();
+        first_seen.record(&blob, "2017-10-02T00:00:00Z");
+
+        let feed = make_feed(&blob, &first_seen, "foo", None).unwrap();
+        assert!(feed.contains("<id>tag:find-work,2017:feed/foo</id>"));
+        assert!(feed.contains("<id>https://github.com/rust-lang-nursery/rustfmt/issues/42</id>"));
+        assert!(feed.contains("<title>rust-lang-nursery/rustfmt#42: Crash on &lt;T&gt; &amp; &quot;U&quot;</title>"));
+        assert!(feed.contains("<updated>2017-10-02T00:00:00Z</updated>"));
+        assert_eq!(feed.matches("<entry>").count(), 1);
+
+        let feed = make_feed(&blob, &first_seen, "foo", Some("rustfmt")).unwrap();
+        assert!(feed.contains("<id>tag:find-work,2017:feed/foo/rustfmt</id>"));
+        assert!(make_feed(&blob, &first_seen, "foo", Some("nothing")).is_none());
+        assert!(make_feed(&blob, &first_seen, "nothing", None).is_none());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b>&'\"\u{1}\n"), "a&lt;b&gt;&amp;&apos;&quot;\n");
+    }
+}
@@ -1,23 +1,63 @@
+use auth::AppToken;
 use config::Config;
 use data::FetchFile;
+use issues::{IssueQuery, IssueSource};
 
 use reqwest::{self, header};
-use std::collections::HashMap;
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
 use std::iter::FromIterator;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
+
+/// The API base url for github.com, used unless the config says otherwise.
+pub const DEFAULT_API_BASE: &str = "https://api.github.com";
+
+// Number of results to request per page for paginated queries (GitHub's maximum).
+const PER_PAGE: usize = 100;
+// Once this few requests remain in the rate limit quota, we wait for it to reset.
+const RATE_LIMIT_RESERVE: u32 = 10;
+// The longest we'll wait (in seconds) for the quota to reset or for a
+// `Retry-After`, rather than giving up on the request.
+const MAX_RATE_LIMIT_WAIT: u64 = 5 * 60;
+// How many times we'll retry a request which hit a secondary rate limit.
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+// How many times we'll retry a request after a server error or a network failure.
+const MAX_TRANSIENT_RETRIES: u32 = 3;
+// The delay before the first retry after a transient failure, in milliseconds.
+// Doubled for each subsequent retry.
+const RETRY_BASE_DELAY: u64 = 1000;
 
 // Client for GitHub API requests.
 pub struct Client<'a> {
     reqwest: reqwest::Client,
     config: &'a Config,
+    state: &'a ClientState,
+    api_base: &'a str,
     cached_milestones: HashMap<String, HashMap<String, u32>>,
+    cached_repositories: HashMap<String, Vec<String>>,
 }
 
 impl<'a> Client<'a> {
-    pub fn new(config: &'a Config) -> ::Result<Client<'a>> {
+    pub fn new(config: &'a Config, state: &'a ClientState) -> ::Result<Client<'a>> {
+        Client::for_api_base(config, state, &config.api_base)
+    }
+
+    /// Make a client for a GitHub instance other than the default one in `config`.
+    pub fn for_api_base(config: &'a Config, state: &'a ClientState, api_base: &'a str) -> ::Result<Client<'a>> {
         Ok(Client {
             reqwest: reqwest::Client::new()?,
             config,
+            state,
+            api_base: api_base.trim_end_matches('/'),
             cached_milestones: HashMap::new(),
+            cached_repositories: HashMap::new(),
         })
     }
 
@@ -27,12 +67,8 @@ impl<'a> Client<'a> {
         }
 
         let query_string = format!("/repos/{}/milestones", repository);
-        let map = self.query(
-            &query_string,
-            |json: Vec<Milestone>| {
-                Ok(HashMap::from_iter(json.into_iter().map(|ms| (ms.title, ms.number))))
-            }
-        )?;
+        let json: Vec<Milestone> = self.query_pages(&query_string, None)?;
+        let map = HashMap::from_iter(json.into_iter().map(|ms| (ms.title, ms.number)));
 
         self.cached_milestones.insert(repository.to_owned(), map);
         Ok(())
@@ -48,16 +84,90 @@ impl<'a> Client<'a> {
         }
     }
 
-    pub fn fetch_issues(&mut self, repository: &str, labels: &str, milestone: Option<&str>) -> ::Result<Vec<Issue>> {
-        let mut query_string = format!("/repos/{}/issues?labels={}", repository, labels);
+    /// Fetch issues with all of `labels`, following pagination.
+    /// If `limit` is given, at most that many issues are returned. If
+    /// `unassigned` is true, only issues without an assignee are returned.
+    ///
+    /// Only issues for which `keep` returns true are returned or count towards
+    /// `limit`. Note that the results may include pull requests, unless `keep`
+    /// drops them.
+    pub fn fetch_issues(
+        &mut self,
+        repository: &str,
+        labels: &[&str],
+        milestone: Option<&str>,
+        limit: Option<usize>,
+        unassigned: bool,
+        keep: &dyn Fn(&Issue) -> bool,
+    ) -> ::Result<Vec<Issue>> {
+        // Labels may contain spaces, commas or `&`, so each is encoded before
+        // they are joined.
+        let labels: Vec<String> = labels.iter()
+            .map(|l| form_urlencoded::byte_serialize(l.as_bytes()).collect())
+            .collect();
+        let mut query_string = format!("/repos/{}/issues?labels={}", repository, labels.join(","));
         if let Some(milestone) = milestone {
             let milestone = self.milestone_number(repository, milestone)?;
             query_string.push_str(&format!("&milestone={}", milestone));
         }
-        self.query(
-            &query_string,
-            |json: Vec<Issue>| { Ok(json) }
-        )
+        if unassigned {
+            query_string.push_str("&assignee=none");
+        }
+        self.query_pages_filtered(&query_string, limit, keep)
+    }
+
+    /// The unarchived repositories of the organisation `org`, as "owner/name".
+    pub fn org_repositories(&mut self, org: &str) -> ::Result<Vec<String>> {
+        if let Some(repositories) = self.cached_repositories.get(org) {
+            return Ok(repositories.clone());
+        }
+
+        let json: Vec<Repository> = self.query_pages(&format!("/orgs/{}/repos", org), None)?;
+        let repositories: Vec<String> = json.into_iter().filter(|r| !r.archived).map(|r| r.full_name).collect();
+        self.cached_repositories.insert(org.to_owned(), repositories.clone());
+        Ok(repositories)
+    }
+
+    /// Make a GraphQL query, returning the `data` from the response.
+    pub fn graphql<T>(&self, query_str: &str) -> ::Result<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        debug!("graphql: `{}`", query_str);
+
+        // GraphQL has its own quota, separate from the REST API's.
+        let url = graphql_url(self.api_base);
+        let body = serde_json::to_string(&GraphqlRequest { query: query_str })?;
+        let mut res = send_with_retries(self.state, &url, || {
+            let req = self.wait_for_quota(&url).and_then(|()| {
+                Ok(self
+                    .reqwest
+                    .post(&url)?
+                    .header(header::UserAgent::new("nrc"))
+                    .header(self.authorization()?)
+                    .header(header::ContentType::json())
+                    .body(body.clone())
+                    .build())
+            });
+            match req {
+                Ok(req) => self.send(req, &url),
+                Err(e) => Attempt::Fatal(e),
+            }
+        })?;
+
+        if !res.status().is_success() {
+            let mut body = String::new();
+            res.read_to_string(&mut body)?;
+            debug!("body: {}", body);
+            return Err(::WorkErr(format!("Server error? {:?}", res.status())));
+        }
+
+        let response: GraphqlResponse<T> = res.json()?;
+        if !response.errors.is_empty() {
+            let messages: Vec<String> = response.errors.into_iter().map(|e| e.message).collect();
+            return Err(::WorkErr(format!("GraphQL errors: {}", messages.join("; "))));
+        }
+        response.data.ok_or_else(|| ::WorkErr("GraphQL response has no data".to_owned()))
     }
 
     fn query<T, U, F>(&self, query_str: &str, f: F) -> ::Result<T>
@@ -67,21 +177,55 @@ impl<'a> Client<'a> {
     {
         debug!("query: `{}`", query_str);
 
-        let url = format!("https://api.github.com{}", query_str);
-        let req = self
-            .reqwest
-            .get(&url)?
-            .header(header::UserAgent::new("nrc"))
-            .header(header::Authorization(format!("token {}", self.config.token)))
-            .build();
-        debug!("request: `{:?}`", req);
+        let url = format!("{}{}", self.api_base, query_str);
+        let page = self.get(&url)?;
+        let json = serde_json::from_str(&page.body)?;
+        f(json)
+    }
 
-        let mut res = self.reqwest.execute(req)?;
-        debug!("response: `{:?}`", res);
+    // Query a list resource, following the `Link` header through every page of
+    // results. Stops early once `limit` items have been collected.
+    fn query_pages<T>(&self, query_str: &str, limit: Option<usize>) -> ::Result<Vec<T>>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        self.query_pages_filtered(query_str, limit, |_| true)
+    }
 
-        if !res.status().is_success() {
-            use std::io::Read;
+    // Like `query_pages`, but only items for which `keep` returns true are
+    // collected, or count towards `limit`.
+    fn query_pages_filtered<T, K>(&self, query_str: &str, limit: Option<usize>, keep: K) -> ::Result<Vec<T>>
+    where
+        T: ::serde::de::DeserializeOwned,
+        K: Fn(&T) -> bool,
+    {
+        debug!("query_pages: `{}`", query_str);
 
+        let separator = if query_str.contains('?') { '&' } else { '?' };
+        let url = format!("{}{}{}per_page={}", self.api_base, query_str, separator, PER_PAGE);
+        collect_pages(url, limit, keep, |url| {
+            let page = self.get(url)?;
+            Ok((serde_json::from_str(&page.body)?, page.next))
+        })
+    }
+
+    // Make a GET request to `url`, returning an error if it was not successful.
+    //
+    // If we have seen `url` before, the request is conditional on the cached
+    // `ETag`/`Last-Modified` and the cached body is reused if nothing changed.
+    fn get(&self, url: &str) -> ::Result<Page> {
+        self.state.used.lock().unwrap().insert(url.to_owned());
+        let cached = self.state.responses.lock().unwrap().get(url).cloned();
+        let mut res = send_with_retries(self.state, url, || self.execute(url, cached.as_ref()))?;
+
+        if res.status() == reqwest::StatusCode::NotModified {
+            if let Some(cached) = cached {
+                debug!("not modified, using cached body for `{}`", url);
+                return Ok(Page { body: cached.body, next: cached.next });
+            }
+        }
+
+        if !res.status().is_success() {
             debug!("Query failed, repsonse: {:?}", res);
 
             let mut body = String::new();
@@ -90,14 +234,323 @@ impl<'a> Client<'a> {
             return Err(::WorkErr(format!("Server error? {:?}", res.status())));
         }
 
-        let json = res.json()?;
-        f(json)
+        let etag = raw_header(res.headers(), "ETag");
+        let last_modified = raw_header(res.headers(), "Last-Modified");
+        let next = next_page(res.headers());
+        let mut body = String::new();
+        res.read_to_string(&mut body)?;
+
+        if etag.is_some() || last_modified.is_some() {
+            let response = CachedResponse {
+                etag,
+                last_modified,
+                body: body.clone(),
+                next: next.clone(),
+            };
+            self.state.responses.lock().unwrap().insert(url.to_owned(), response);
+        }
+
+        Ok(Page { body, next })
+    }
+
+    // Make a single GET request to `url`, conditional on `cached` if present.
+    // Waits first if we are nearly out of quota.
+    fn execute(&self, url: &str, cached: Option<&CachedResponse>) -> Attempt {
+        match self.wait_for_quota(self.api_base).and_then(|()| self.get_request(url, cached)) {
+            Ok(req) => self.send(req, self.api_base),
+            Err(e) => Attempt::Fatal(e),
+        }
+    }
+
+    fn get_request(&self, url: &str, cached: Option<&CachedResponse>) -> ::Result<reqwest::Request> {
+        let mut conditions = header::Headers::new();
+        if let Some(cached) = cached {
+            if let Some(ref etag) = cached.etag {
+                conditions.set_raw("If-None-Match", etag.clone());
+            }
+            if let Some(ref last_modified) = cached.last_modified {
+                conditions.set_raw("If-Modified-Since", last_modified.clone());
+            }
+        }
+
+        Ok(self
+            .reqwest
+            .get(url)?
+            .header(header::UserAgent::new("nrc"))
+            .header(self.authorization()?)
+            .headers(conditions)
+            .build())
+    }
+
+    // Send `req`, recording the rate limit quota from the response under
+    // `quota` (the API base url, or the GraphQL url).
+    fn send(&self, req: reqwest::Request, quota: &str) -> Attempt {
+        debug!("request: `{:?}`", req);
+        let res = match self.reqwest.execute(req) {
+            Ok(res) => res,
+            Err(e) => return Attempt::Retryable(e),
+        };
+        debug!("response: `{:?}`", res);
+
+        if let Some(rate_limit) = RateLimit::from_headers(res.headers()) {
+            self.state.rate_limits.lock().unwrap().insert(quota.to_owned(), rate_limit);
+        }
+
+        Attempt::Response(Box::new(res))
+    }
+
+    fn authorization(&self) -> ::Result<header::Authorization<String>> {
+        // The app is installed on the default GitHub instance.
+        if let Some(ref app) = self.config.app {
+            if self.api_base == self.config.api_base.trim_end_matches('/') {
+                let token = self.state.app_token.get(&self.reqwest, self.api_base, app)?;
+                return Ok(header::Authorization(format!("token {}", token)));
+            }
+        }
+
+        let token = self.config.api_tokens.get(self.api_base).unwrap_or(&self.config.token);
+        Ok(header::Authorization(format!("token {}", token)))
+    }
+
+    // If the last response using `quota` said we are nearly out of it, wait for
+    // it to reset. If the reset is too far away, give up instead.
+    fn wait_for_quota(&self, quota: &str) -> ::Result<()> {
+        let rate_limit = match self.state.rate_limits.lock().unwrap().get(quota) {
+            Some(rate_limit) => *rate_limit,
+            None => return Ok(()),
+        };
+        if rate_limit.remaining > RATE_LIMIT_RESERVE {
+            return Ok(());
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if rate_limit.reset <= now {
+            return Ok(());
+        }
+        let wait = rate_limit.reset - now;
+        if wait > MAX_RATE_LIMIT_WAIT {
+            return Err(::WorkErr(format!(
+                "GitHub rate limit nearly exhausted ({} of {} remaining), resets in {}s",
+                rate_limit.remaining,
+                rate_limit.limit,
+                wait,
+            )));
+        }
+
+        warn!("GitHub rate limit nearly exhausted, waiting {}s for reset", wait);
+        thread::sleep(Duration::from_secs(wait));
+        Ok(())
+    }
+}
+
+/// The outcome of sending a request once.
+pub enum Attempt {
+    /// The server responded, possibly with an error status.
+    Response(Box<reqwest::Response>),
+    /// No response was received, e.g., because of a timeout or a connection
+    /// reset. Trying again might work.
+    Retryable(reqwest::Error),
+    /// Trying again won't help, e.g., the url is bad or the quota is used up.
+    Fatal(::WorkErr),
+}
+
+/// Send a request by calling `send`, which makes a single attempt, retrying
+/// after network failures, server errors, and secondary rate limits. The
+/// response may still have an error status if retrying didn't help.
+pub fn send_with_retries<F>(state: &ClientState, url: &str, mut send: F) -> ::Result<reqwest::Response>
+where
+    F: FnMut() -> Attempt,
+{
+    let mut retries = 0;
+    let mut transient_retries = 0;
+    loop {
+        let res = match send() {
+            Attempt::Response(res) => *res,
+            Attempt::Retryable(e) => {
+                if transient_retries < MAX_TRANSIENT_RETRIES {
+                    retry_after_backoff(state, url, &e, &mut transient_retries);
+                    continue;
+                }
+                return Err(e.into());
+            }
+            Attempt::Fatal(e) => return Err(e),
+        };
+
+        // 5xx errors are usually temporary, but 4xx errors won't go away
+        // by trying again.
+        if res.status().is_server_error() && transient_retries < MAX_TRANSIENT_RETRIES {
+            retry_after_backoff(state, url, &res.status(), &mut transient_retries);
+            continue;
+        }
+
+        // Secondary rate limits are reported with a `Retry-After` header.
+        let status = res.status();
+        if status == reqwest::StatusCode::Forbidden || status == reqwest::StatusCode::TooManyRequests {
+            if let Some(wait) = header_number::<u64>(res.headers(), "Retry-After") {
+                if retries < MAX_RATE_LIMIT_RETRIES && wait <= MAX_RATE_LIMIT_WAIT {
+                    retries += 1;
+                    warn!("Rate limited, retrying `{}` in {}s", url, wait);
+                    thread::sleep(Duration::from_secs(wait));
+                    continue;
+                }
+            }
+        }
+
+        return Ok(res);
     }
 }
 
-impl<'a> FetchFile for Client<'a> {
+fn retry_after_backoff<E: ::std::fmt::Debug>(state: &ClientState, url: &str, error: &E, retries: &mut u32) {
+    let delay = backoff(*retries);
+    *retries += 1;
+    state.retries.fetch_add(1, Ordering::Relaxed);
+    warn!(
+        "Request to `{}` failed ({:?}), retry {} of {} in {}ms",
+        url,
+        error,
+        retries,
+        MAX_TRANSIENT_RETRIES,
+        delay.as_secs() * 1000 + u64::from(delay.subsec_nanos()) / 1_000_000,
+    );
+    thread::sleep(delay);
+}
+
+// The body of a single response, and the url of the next page of results if
+// there is one.
+struct Page {
+    body: String,
+    next: Option<String>,
+}
+
+// How long to wait before retry number `retries` (counting from zero):
+// exponential backoff, plus up to 50% jitter.
+fn backoff(retries: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY << retries;
+    // We only need enough randomness to stop clients retrying in lockstep.
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| u64::from(d.subsec_nanos()))
+        .unwrap_or(0);
+    Duration::from_millis(delay + nanos % (delay / 2 + 1))
+}
+
+fn raw_header(headers: &header::Headers, name: &str) -> Option<String> {
+    headers.get_raw(name)
+        .and_then(|raw| raw.one())
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn header_number<T: FromStr>(headers: &header::Headers, name: &str) -> Option<T> {
+    raw_header(headers, name).and_then(|s| s.trim().parse().ok())
+}
+
+/// The url of the website corresponding to a GitHub API base url, e.g.,
+/// `https://github.com` for `https://api.github.com`, or `https://ghe.example.com`
+/// for `https://ghe.example.com/api/v3`.
+pub fn web_base(api_base: &str) -> String {
+    let api_base = api_base.trim_end_matches('/');
+    if let Some(web_base) = api_base.strip_suffix("/api/v3") {
+        return web_base.to_owned();
+    }
+    match api_base.find("://api.") {
+        Some(i) => format!("{}://{}", &api_base[..i], &api_base[i + "://api.".len()..]),
+        None => api_base.to_owned(),
+    }
+}
+
+// The GraphQL endpoint for a GitHub API base url.
+fn graphql_url(api_base: &str) -> String {
+    format!("{}/graphql", api_base.strip_suffix("/v3").unwrap_or(api_base))
+}
+
+/// Collect the items of a paginated resource, starting from `url`. `fetch_page`
+/// returns the items in a page and the url of the next page, if any. Items are
+/// only collected if `keep` returns true, and we stop once there are `limit` of
+/// them, so filtered out items don't use up the limit.
+pub fn collect_pages<T, K, F>(url: String, limit: Option<usize>, keep: K, mut fetch_page: F) -> ::Result<Vec<T>>
+where
+    K: Fn(&T) -> bool,
+    F: FnMut(&str) -> ::Result<(Vec<T>, Option<String>)>,
+{
+    let mut next = Some(url);
+    let mut result = vec![];
+    while let Some(url) = next {
+        let (items, next_url) = fetch_page(&url)?;
+        next = next_url;
+        result.extend(items.into_iter().filter(|i| keep(i)));
+
+        if let Some(limit) = limit {
+            if result.len() >= limit {
+                result.truncate(limit);
+                break;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The url of the next page of results, taken from the `Link` header of a response.
+pub fn next_page(headers: &header::Headers) -> Option<String> {
+    let link = headers.get::<header::Link>()?;
+    link.values()
+        .iter()
+        .find(|v| v.rel().is_some_and(|rel| rel.contains(&header::RelationType::Next)))
+        .map(|v| v.link().to_owned())
+}
+
+/// Reads files from a directory of a GitHub repository.
+pub struct RepoDir<'a> {
+    client: Client<'a>,
+    repository: String,
+    dir: String,
+    // The branch, tag, or commit to read from, the default branch if `None`.
+    git_ref: Option<String>,
+}
+
+impl<'a> RepoDir<'a> {
+    pub fn new(client: Client<'a>, repository: &str, dir: &str, git_ref: Option<&str>) -> RepoDir<'a> {
+        RepoDir {
+            client,
+            repository: repository.to_owned(),
+            dir: dir.trim_matches('/').to_owned(),
+            git_ref: git_ref.map(|r| r.to_owned()),
+        }
+    }
+
+    /// Resolve the ref to a commit, and read files from that commit from now
+    /// on, so that every file comes from the same commit even if the ref moves
+    /// meanwhile. Returns the commit's SHA.
+    pub fn pin_ref(&mut self) -> ::Result<String> {
+        let query_string = format!(
+            "/repos/{}/commits/{}",
+            self.repository,
+            self.git_ref.as_ref().map_or("HEAD", |r| &**r),
+        );
+        let sha = self.client.query(&query_string, |json: Commit| Ok(json.sha))?;
+        self.git_ref = Some(sha.clone());
+        Ok(sha)
+    }
+
+    // The contents API query string for `path` in `dir`.
+    fn contents_query(&self, path: &str) -> String {
+        let mut result = format!("/repos/{}/contents/{}", self.repository, self.dir);
+        if !path.is_empty() {
+            if !self.dir.is_empty() {
+                result.push('/');
+            }
+            result.push_str(path);
+        }
+        if let Some(ref r) = self.git_ref {
+            let r: String = form_urlencoded::byte_serialize(r.as_bytes()).collect();
+            result.push_str(&format!("?ref={}", r));
+        }
+        result
+    }
+}
+
+impl<'a> FetchFile for RepoDir<'a> {
     fn fetch_file(&self, path: &str) -> ::Result<String> {
-        self.query(&format!("/repos/{}/contents/{}", self.config.repository, path), |json: File| {
+        self.client.query(&self.contents_query(path), |json: File| {
             if json.type_ != "file" {
                 return Err(::WorkErr(format!("Expected file, found {}", json.type_)));
             }
@@ -107,6 +560,165 @@ impl<'a> FetchFile for Client<'a> {
             Ok(String::from_utf8(::base64::decode_config(&json.content, ::base64::MIME)?)?)
         })
     }
+
+    fn list_files(&self) -> ::Result<Vec<String>> {
+        self.client.query(&self.contents_query(""), |json: Vec<DirEntry>| {
+            Ok(json.into_iter().filter(|e| e.type_ == "file").map(|e| e.name).collect())
+        })
+    }
+}
+
+impl<'a> IssueSource for Client<'a> {
+    fn fetch_issues(&mut self, query: &IssueQuery, keep: &dyn Fn(&Issue) -> bool) -> ::Result<Vec<Issue>> {
+        Client::fetch_issues(
+            self,
+            query.repository,
+            &query.labels,
+            query.milestone,
+            query.limit,
+            query.unassigned,
+            keep,
+        )
+    }
+
+    fn list_repositories(&mut self, owner: &str) -> ::Result<Vec<String>> {
+        self.org_repositories(owner)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GraphqlRequest<'a> {
+    query: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+/// State shared by GitHub (and GitLab) clients and kept between refreshes.
+#[derive(Debug, Default)]
+pub struct ClientState {
+    responses: Mutex<HashMap<String, CachedResponse>>,
+    // The urls requested since unused responses were last evicted.
+    used: Mutex<HashSet<String>>,
+    rate_limits: Mutex<HashMap<String, RateLimit>>,
+    retries: AtomicUsize,
+    app_token: AppToken,
+}
+
+impl ClientState {
+    /// The rate limit quota reported by the most recent response from each
+    /// GitHub instance, keyed by API base url. GraphQL quotas are keyed by the
+    /// GraphQL url.
+    pub fn rate_limits(&self) -> HashMap<String, RateLimit> {
+        self.rate_limits.lock().unwrap().clone()
+    }
+
+    /// The number of requests which have been retried after a transient
+    /// failure, since the server started.
+    pub fn retries(&self) -> usize {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// Make a new state, using the response cache saved by a previous run if
+    /// there is one.
+    pub fn load(config: &Config) -> ClientState {
+        let mut result = ClientState::default();
+        if let Some(ref path) = config.cache_path {
+            match read_response_cache(path) {
+                Ok(responses) => result.responses = Mutex::new(responses),
+                Err(e) => info!("Could not read response cache `{}`: {}", path, e.0),
+            }
+        }
+        result
+    }
+
+    /// Drop any cached responses which haven't been used since this was last
+    /// called. Only call this after a full refresh; a partial refresh doesn't
+    /// request the urls for the issues it reuses, but they are still needed.
+    pub fn evict_unused(&self) {
+        let mut used = self.used.lock().unwrap();
+        self.responses.lock().unwrap().retain(|url, _| used.contains(url));
+        used.clear();
+    }
+
+    /// Write the response cache to disk, if the config has a `cache_path`.
+    pub fn save(&self, config: &Config) -> ::Result<()> {
+        if let Some(ref path) = config.cache_path {
+            let file = fs::File::create(path)?;
+            serde_json::to_writer(file, &*self.responses.lock().unwrap())?;
+        }
+        Ok(())
+    }
+
+    // The urls with cached responses, for tests in other modules.
+    #[cfg(test)]
+    pub fn cached_urls(&self) -> Vec<String> {
+        self.responses.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+/// GitHub's rate limit quota, as reported in the headers of a response.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    /// When the quota resets, in seconds since the Unix epoch.
+    pub reset: u64,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &header::Headers) -> Option<RateLimit> {
+        Some(RateLimit {
+            limit: header_number(headers, "X-RateLimit-Limit")?,
+            remaining: header_number(headers, "X-RateLimit-Remaining")?,
+            reset: header_number(headers, "X-RateLimit-Reset")?,
+        })
+    }
+}
+
+fn read_response_cache(path: &str) -> ::Result<HashMap<String, CachedResponse>> {
+    let file = fs::File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+// A previous response to a GET request, used for conditional requests.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+    next: Option<String>,
+}
+
+/// An entry in a directory listing, returned by the GitHub API.
+#[derive(Debug, Deserialize)]
+struct DirEntry {
+    #[serde(rename="type")]
+    type_: String,
+    name: String,
+}
+
+/// A commit, returned by the GitHub API.
+#[derive(Debug, Deserialize)]
+struct Commit {
+    sha: String,
+}
+
+/// A repository, returned by the GitHub API.
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+    #[serde(default)]
+    archived: bool,
 }
 
 /// A file, returned by the GitHub API.
@@ -122,13 +734,49 @@ struct File {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Issue {
     pub number: u32,
+    /// "owner/name", filled in once the issue has been fetched.
+    #[serde(default)]
+    pub repository: String,
     #[serde(rename="html_url")]
     pub url: String,
     pub title: String,
     pub body: String,
     pub labels: Vec<Label>,
+    /// "open" or "closed".
+    pub state: String,
+    pub assignees: Vec<User>,
+    /// The author of the issue.
+    #[serde(rename="user")]
+    pub author: User,
+    /// The author's relationship with the repository, e.g., "MEMBER" or "NONE".
+    pub author_association: String,
+    pub milestone: Option<Milestone>,
+    /// The number of comments on the issue.
+    pub comments: u32,
+    /// ISO 8601, e.g., "2017-10-02T19:15:59Z".
+    pub created_at: String,
+    /// ISO 8601, e.g., "2017-10-02T19:15:59Z".
+    pub updated_at: String,
+    /// Present if the issue is a pull request.
+    #[serde(default, skip_serializing)]
+    pub pull_request: Option<PullRequestLinks>,
+}
+
+impl Issue {
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    pub fn is_assigned(&self) -> bool {
+        !self.assignees.is_empty()
+    }
 }
 
+/// The links GitHub adds to an issue which is a pull request. We only care
+/// whether they are there.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PullRequestLinks {}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Label {
     pub url: String,
@@ -136,10 +784,17 @@ pub struct Label {
     pub color: String,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct Milestone {
-    number: u32,
-    title: String,
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct User {
+    pub login: String,
+    #[serde(rename="html_url")]
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Milestone {
+    pub number: u32,
+    pub title: String,
 }
 
 #[cfg(test)]
@@ -151,7 +806,8 @@ mod test {
     where F: FnOnce(&mut Client)
     {
         let config = mock_config();
-        let mut client = Client::new(&config).unwrap_or_else(|s| panic!("{:?}", s));
+        let state = ClientState::default();
+        let mut client = Client::new(&config, &state).unwrap_or_else(|s| panic!("{:?}", s));
         f(&mut client);
     }
 
@@ -179,17 +835,38 @@ mod test {
     }
 
     #[test]
-    fn test_fetch_file() {
-        mock_client(|client| {
-            let contents = client.fetch_file("back/test-token.txt.example").unwrap_or_else(|s| panic!("{:?}", s));
-            assert_eq!(contents, "Put your GitHub token here\n");
-        });
+    fn test_contents_query() {
+        let config = mock_config();
+        let state = ClientState::default();
+        let client = Client::new(&config, &state).unwrap_or_else(|s| panic!("{:?}", s));
+        let dir = RepoDir::new(client, "nrc/find-work", "", Some("release/1.0+fixes"));
+        assert_eq!(dir.contents_query("tabs.json"), "/repos/nrc/find-work/contents/tabs.json?ref=release%2F1.0%2Bfixes");
+    }
+
+    #[test]
+    fn test_repo_dir() {
+        let config = mock_config();
+        let state = ClientState::default();
+        let client = Client::new(&config, &state).unwrap_or_else(|s| panic!("{:?}", s));
+        let mut dir = RepoDir::new(client, "nrc/find-work", "back", None);
+        assert_eq!(dir.contents_query("test-token.txt.example"), "/repos/nrc/find-work/contents/back/test-token.txt.example");
+
+        let files = dir.list_files().unwrap_or_else(|s| panic!("{:?}", s));
+        assert!(files.contains(&"Cargo.toml".to_owned()));
+        let contents = dir.fetch_file("test-token.txt.example").unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(contents, "Put your GitHub token here\n");
+
+        let sha = dir.pin_ref().unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(sha.len(), 40);
+        assert_eq!(dir.contents_query(""), format!("/repos/nrc/find-work/contents/back?ref={}", sha));
+        let contents = dir.fetch_file("test-token.txt.example").unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(contents, "Put your GitHub token here\n");
     }
 
     #[test]
     fn test_fetch_issues() {
         mock_client(|client| {
-            let issues = client.fetch_issues("nrc/testing", "label-1,label-2", None).unwrap_or_else(|s| panic!("{:?}", s));
+            let issues = client.fetch_issues("nrc/testing", &["label-1", "label-2"], None, None, false, &|_| true).unwrap_or_else(|s| panic!("{:?}", s));
             assert_eq!(issues.len(), 1);
             assert_eq!(issues[0].number, 2);
             assert_eq!(issues[0].url, "https://github.com/nrc/testing/issues/2");
@@ -198,4 +875,180 @@ mod test {
             assert_eq!(issues[0].labels.len(), 2);
         });
     }
+
+    #[test]
+    fn test_org_repositories() {
+        mock_client(|client| {
+            let repositories = client.org_repositories("rust-lang").unwrap_or_else(|s| panic!("{:?}", s));
+            assert!(repositories.contains(&"rust-lang/rust".to_owned()));
+            assert!(client.cached_repositories.contains_key("rust-lang"));
+        });
+    }
+
+    #[test]
+    fn test_conditional_query() {
+        mock_client(|client| {
+            let url = "https://api.github.com/repos/nrc/find-work/contents/data";
+            let first = client.get(url).unwrap_or_else(|s| panic!("{:?}", s));
+            assert!(client.state.responses.lock().unwrap().contains_key(url));
+            // The second request should be answered from the cache.
+            let second = client.get(url).unwrap_or_else(|s| panic!("{:?}", s));
+            assert_eq!(first.body, second.body);
+        });
+    }
+
+    fn cached_response(body: &str) -> CachedResponse {
+        CachedResponse {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+            body: body.to_owned(),
+            next: None,
+        }
+    }
+
+    #[test]
+    fn test_save_response_cache() {
+        let mut config = mock_config();
+        // Unique to this test and process, so that test runs don't interfere.
+        let path = ::std::env::temp_dir().join(format!("find-work-test-save-response-cache-{}.json", ::std::process::id()));
+        config.cache_path = Some(path.to_str().unwrap().to_owned());
+
+        let state = ClientState::default();
+        state.responses.lock().unwrap().insert("https://example.com".to_owned(), cached_response("[]"));
+        state.used.lock().unwrap().insert("https://example.com".to_owned());
+        state.save(&config).unwrap_or_else(|s| panic!("{:?}", s));
+
+        let loaded = ClientState::load(&config);
+        fs::remove_file(&path).unwrap();
+        let responses = loaded.responses.lock().unwrap();
+        assert_eq!(responses["https://example.com"].etag, Some("\"abc\"".to_owned()));
+        assert_eq!(responses["https://example.com"].body, "[]");
+    }
+
+    #[test]
+    fn test_evict_response_cache() {
+        let config = mock_config();
+        let state = ClientState::default();
+        state.responses.lock().unwrap().insert("https://example.com/used".to_owned(), cached_response("[1]"));
+        state.responses.lock().unwrap().insert("https://example.com/unused".to_owned(), cached_response("[2]"));
+        state.used.lock().unwrap().insert("https://example.com/used".to_owned());
+
+        // Saving alone doesn't evict anything.
+        state.save(&config).unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(state.responses.lock().unwrap().len(), 2);
+
+        state.evict_unused();
+        assert_eq!(state.cached_urls(), vec!["https://example.com/used"]);
+
+        // Nothing was used since the last eviction.
+        state.evict_unused();
+        assert!(state.cached_urls().is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_issue() {
+        let json = r#"{
+            "number": 1347,
+            "html_url": "https://github.com/octocat/Hello-World/issues/1347",
+            "title": "Found a bug",
+            "body": "I'm having a problem with this.",
+            "labels": [],
+            "state": "open",
+            "assignees": [{ "login": "octocat", "html_url": "https://github.com/octocat" }],
+            "user": { "login": "hubot", "html_url": "https://github.com/hubot" },
+            "author_association": "CONTRIBUTOR",
+            "milestone": { "number": 1, "title": "v1.0" },
+            "comments": 3,
+            "created_at": "2011-04-22T13:33:48Z",
+            "updated_at": "2011-04-23T13:33:48Z"
+        }"#;
+        let issue: Issue = serde_json::from_str(json).unwrap_or_else(|s| panic!("{:?}", s));
+        assert_eq!(issue.assignees.len(), 1);
+        assert_eq!(issue.assignees[0].login, "octocat");
+        assert_eq!(issue.author.login, "hubot");
+        assert_eq!(issue.author_association, "CONTRIBUTOR");
+        assert_eq!(issue.milestone.as_ref().unwrap().title, "v1.0");
+        assert_eq!(issue.comments, 3);
+        assert_eq!(issue.updated_at, "2011-04-23T13:33:48Z");
+        assert!(!issue.is_pull_request());
+        assert!(issue.is_assigned());
+    }
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let mut headers = header::Headers::new();
+        assert_eq!(RateLimit::from_headers(&headers), None);
+
+        headers.set_raw("X-RateLimit-Limit", "5000");
+        headers.set_raw("X-RateLimit-Remaining", "4987");
+        headers.set_raw("X-RateLimit-Reset", "1372700873");
+        assert_eq!(RateLimit::from_headers(&headers), Some(RateLimit {
+            limit: 5000,
+            remaining: 4987,
+            reset: 1372700873,
+        }));
+    }
+
+    #[test]
+    fn test_wait_for_quota() {
+        mock_client(|client| {
+            client.wait_for_quota(DEFAULT_API_BASE).unwrap_or_else(|s| panic!("{:?}", s));
+
+            // Exhausted, and not resetting for an hour.
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            client.state.rate_limits.lock().unwrap().insert(DEFAULT_API_BASE.to_owned(), RateLimit {
+                limit: 5000,
+                remaining: 0,
+                reset: now + 60 * 60,
+            });
+            assert!(client.wait_for_quota(DEFAULT_API_BASE).is_err());
+            // GraphQL's quota is separate.
+            client.wait_for_quota(&graphql_url(DEFAULT_API_BASE)).unwrap_or_else(|s| panic!("{:?}", s));
+
+            // Plenty left.
+            client.state.rate_limits.lock().unwrap().insert(DEFAULT_API_BASE.to_owned(), RateLimit {
+                limit: 5000,
+                remaining: 4000,
+                reset: now + 60 * 60,
+            });
+            client.wait_for_quota(DEFAULT_API_BASE).unwrap_or_else(|s| panic!("{:?}", s));
+        });
+    }
+
+    #[test]
+    fn test_backoff() {
+        for retries in 0..MAX_TRANSIENT_RETRIES {
+            let delay = backoff(retries);
+            let min = Duration::from_millis(RETRY_BASE_DELAY << retries);
+            assert!(delay >= min);
+            assert!(delay <= min + min / 2);
+        }
+    }
+
+    #[test]
+    fn test_web_base() {
+        assert_eq!(web_base("https://api.github.com"), "https://github.com");
+        assert_eq!(web_base("https://api.github.com/"), "https://github.com");
+        assert_eq!(web_base("https://ghe.example.com/api/v3"), "https://ghe.example.com");
+        assert_eq!(web_base("http://localhost:8080"), "http://localhost:8080");
+    }
+
+    #[test]
+    fn test_graphql_url() {
+        assert_eq!(graphql_url("https://api.github.com"), "https://api.github.com/graphql");
+        assert_eq!(graphql_url("https://ghe.example.com/api/v3"), "https://ghe.example.com/api/graphql");
+    }
+
+    #[test]
+    fn test_next_page() {
+        let mut headers = header::Headers::new();
+        assert_eq!(next_page(&headers), None);
+
+        headers.set_raw("Link", "<https://api.github.com/repositories/1/issues?page=2>; rel=\"next\", \
+                                 <https://api.github.com/repositories/1/issues?page=5>; rel=\"last\"");
+        assert_eq!(next_page(&headers), Some("https://api.github.com/repositories/1/issues?page=2".to_owned()));
+
+        headers.set_raw("Link", "<https://api.github.com/repositories/1/issues?page=1>; rel=\"first\"");
+        assert_eq!(next_page(&headers), None);
+    }
 }
@@ -0,0 +1,260 @@
+use config::Config;
+use github::{self, Attempt, ClientState, Issue, Label, Milestone, User};
+use issues::{IssueQuery, IssueSource};
+
+use reqwest::{self, header};
+use std::io::Read;
+use url::form_urlencoded;
+
+/// The API base url for gitlab.com.
+pub const DEFAULT_API_BASE: &str = "https://gitlab.com/api/v4";
+
+// Number of results to request per page (GitLab's maximum).
+const PER_PAGE: usize = 100;
+
+// Client for GitLab API requests.
+pub struct Client<'a> {
+    reqwest: reqwest::Client,
+    config: &'a Config,
+    state: &'a ClientState,
+    api_base: &'a str,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(config: &'a Config, state: &'a ClientState, api_base: &'a str) -> ::Result<Client<'a>> {
+        Ok(Client {
+            reqwest: reqwest::Client::new()?,
+            config,
+            state,
+            api_base: api_base.trim_end_matches('/'),
+        })
+    }
+
+    // Query a list resource, following the `Link` header through every page of
+    // results. Each item is converted with `convert`, and only collected if
+    // `keep` returns true. Stops early once `limit` items have been collected.
+    fn query_pages<T, U, C, K>(&self, query_str: &str, limit: Option<usize>, convert: C, keep: K) -> ::Result<Vec<U>>
+    where
+        T: ::serde::de::DeserializeOwned,
+        C: Fn(T) -> U,
+        K: Fn(&U) -> bool,
+    {
+        debug!("query_pages: `{}`", query_str);
+
+        let separator = if query_str.contains('?') { '&' } else { '?' };
+        let url = format!("{}{}{}per_page={}", self.api_base, query_str, separator, PER_PAGE);
+        github::collect_pages(url, limit, keep, |url| {
+            let mut res = github::send_with_retries(self.state, url, || self.execute(url))?;
+
+            if !res.status().is_success() {
+                let mut body = String::new();
+                res.read_to_string(&mut body)?;
+                debug!("body: {}", body);
+                return Err(::WorkErr(format!("Server error? {:?}", res.status())));
+            }
+
+            let next = github::next_page(res.headers());
+            let page: Vec<T> = res.json()?;
+            Ok((page.into_iter().map(&convert).collect(), next))
+        })
+    }
+
+    // Make a single GET request to `url`.
+    fn execute(&self, url: &str) -> Attempt {
+        let req = match self.reqwest.get(url) {
+            Ok(mut req) => {
+                req.header(header::UserAgent::new("nrc"));
+                // Public projects can be read without a token.
+                if let Some(token) = self.config.api_tokens.get(self.api_base) {
+                    let mut auth = header::Headers::new();
+                    auth.set_raw("PRIVATE-TOKEN", token.clone());
+                    req.headers(auth);
+                }
+                req.build()
+            }
+            Err(e) => return Attempt::Fatal(e.into()),
+        };
+        debug!("request: `{:?}`", req);
+
+        match self.reqwest.execute(req) {
+            Ok(res) => {
+                debug!("response: `{:?}`", res);
+                Attempt::Response(Box::new(res))
+            }
+            Err(e) => Attempt::Retryable(e),
+        }
+    }
+}
+
+impl<'a> IssueSource for Client<'a> {
+    fn fetch_issues(&mut self, query: &IssueQuery, keep: &dyn Fn(&Issue) -> bool) -> ::Result<Vec<Issue>> {
+        // Project paths are used in place of ids, with the `/` encoded.
+        let project = query.repository.replace('/', "%2F");
+        let labels: Vec<String> = query.labels.iter().map(|l| encode(l)).collect();
+        let mut query_string = format!(
+            "/projects/{}/issues?state=opened&with_labels_details=true&labels={}",
+            project,
+            labels.join(","),
+        );
+        if let Some(milestone) = query.milestone {
+            query_string.push_str(&format!("&milestone={}", encode(milestone)));
+        }
+        if query.unassigned {
+            query_string.push_str("&assignee_id=None");
+        }
+
+        let project_url = format!("{}/{}", web_base(self.api_base), query.repository);
+        self.query_pages(&query_string, query.limit, |i: GitlabIssue| i.into_issue(&project_url), keep)
+    }
+
+    fn list_repositories(&mut self, owner: &str) -> ::Result<Vec<String>> {
+        let query_string = format!(
+            "/groups/{}/projects?archived=false&include_subgroups=true",
+            owner.replace('/', "%2F"),
+        );
+        self.query_pages(&query_string, None, |p: GitlabProject| p.path_with_namespace, |_| true)
+    }
+}
+
+// Encode a value for use in a query string. Labels and milestones may contain
+// spaces, `&`, etc.
+fn encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// The url of the website corresponding to a GitLab API base url, e.g.,
+/// `https://gitlab.com` for `https://gitlab.com/api/v4`.
+pub fn web_base(api_base: &str) -> String {
+    let api_base = api_base.trim_end_matches('/');
+    api_base.strip_suffix("/api/v4").unwrap_or(api_base).to_owned()
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabIssue {
+    iid: u32,
+    web_url: String,
+    title: String,
+    description: Option<String>,
+    labels: Vec<GitlabLabel>,
+    state: String,
+    assignees: Vec<GitlabUser>,
+    author: GitlabUser,
+    milestone: Option<GitlabMilestone>,
+    user_notes_count: u32,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabProject {
+    path_with_namespace: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabLabel {
+    name: String,
+    color: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabUser {
+    username: String,
+    web_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitlabMilestone {
+    iid: u32,
+    title: String,
+}
+
+impl GitlabIssue {
+    // Convert to the same model as issues from GitHub.
+    fn into_issue(self, project_url: &str) -> Issue {
+        Issue {
+            number: self.iid,
+            repository: String::new(),
+            url: self.web_url,
+            title: self.title,
+            body: self.description.unwrap_or_default(),
+            labels: self.labels.into_iter().map(|l| Label {
+                url: format!("{}/issues?label_name[]={}", project_url, encode(&l.name)),
+                // GitHub colours don't have the leading `#`.
+                color: l.color.trim_start_matches('#').to_owned(),
+                name: l.name,
+            }).collect(),
+            state: match &*self.state {
+                "opened" => "open".to_owned(),
+                _ => self.state,
+            },
+            assignees: self.assignees.into_iter().map(GitlabUser::into_user).collect(),
+            author: self.author.into_user(),
+            // GitLab has no equivalent.
+            author_association: "NONE".to_owned(),
+            milestone: self.milestone.map(|m| Milestone {
+                number: m.iid,
+                title: m.title,
+            }),
+            comments: self.user_notes_count,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            // Merge requests are never returned as issues.
+            pull_request: None,
+        }
+    }
+}
+
+impl GitlabUser {
+    fn into_user(self) -> User {
+        User {
+            login: self.username,
+            url: self.web_url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_web_base() {
+        assert_eq!(web_base("https://gitlab.com/api/v4"), "https://gitlab.com");
+        assert_eq!(web_base("https://gitlab.example.com/api/v4/"), "https://gitlab.example.com");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("good first issue"), "good+first+issue");
+        assert_eq!(encode("P&L"), "P%26L");
+    }
+
+    #[test]
+    fn test_into_issue() {
+        let json = r##"{
+            "iid": 6,
+            "web_url": "https://gitlab.com/foo/bar/issues/6",
+            "title": "Ut commodi ullam eos dolores perferendis nihil sunt.",
+            "description": null,
+            "labels": [{ "name": "good first issue", "color": "#428BCA" }],
+            "state": "opened",
+            "assignees": [],
+            "author": { "username": "root", "web_url": "https://gitlab.com/root" },
+            "milestone": { "iid": 3, "title": "v2.0" },
+            "user_notes_count": 1,
+            "created_at": "2016-01-04T15:31:46.176Z",
+            "updated_at": "2016-01-04T15:31:46.176Z"
+        }"##;
+        let issue: GitlabIssue = serde_json::from_str(json).unwrap_or_else(|s| panic!("{:?}", s));
+        let issue = issue.into_issue("https://gitlab.com/foo/bar");
+        assert_eq!(issue.number, 6);
+        assert_eq!(issue.body, "");
+        assert_eq!(issue.state, "open");
+        assert_eq!(issue.author.login, "root");
+        assert_eq!(issue.labels[0].color, "428BCA");
+        assert_eq!(issue.labels[0].url, "https://gitlab.com/foo/bar/issues?label_name[]=good+first+issue");
+        assert_eq!(issue.milestone.unwrap().number, 3);
+        assert_eq!(issue.comments, 1);
+    }
+}
@@ -0,0 +1,359 @@
+// Fetch issues using GitHub's GraphQL API. Rather than one REST request per
+// tab-category pair, we batch many repository queries into each request.
+
+use config::Config;
+use data::{Category, Host, StructuralData, TabCategory};
+use github::{self, ClientState, Issue, Label, Milestone, User};
+use issues::{self, IssueData, IssueQuery};
+
+use std::cmp;
+use std::collections::HashMap;
+
+use serde_json;
+
+// How many repository queries to make in a single GraphQL request.
+const BATCH_SIZE: usize = 20;
+// The most issues GitHub will return in one page.
+const PAGE_SIZE: usize = 100;
+
+pub fn fetch_issues(config: &Config, client_state: &ClientState, struct_data: &StructuralData) -> ::Result<IssueData> {
+    // One client per GitHub instance.
+    let mut clients = HashMap::new();
+    // The repositories for each tab-category pair, with wildcards expanded.
+    let mut targets = vec![];
+    for tcs in struct_data.tab_category.values() {
+        for tc in tcs {
+            // Validation should have removed these, but skip them rather than crash.
+            let category = match struct_data.categories.get(&tc.category) {
+                Some(category) => category,
+                None => {
+                    warn!("Unknown category `{}` in tab `{}`, skipping", tc.category, tc.tab);
+                    continue;
+                }
+            };
+            if category.host != Host::GitHub {
+                continue;
+            }
+
+            let api_base = category.api_base(config);
+            if !clients.contains_key(api_base) {
+                clients.insert(api_base, github::Client::for_api_base(config, client_state, api_base)?);
+            }
+
+            let repositories = issues::expand_repositories(clients.get_mut(api_base).unwrap(), category)?;
+            targets.push((category, tc, api_base, repositories));
+        }
+    }
+
+    let mut queries = vec![];
+    for &(category, tc, api_base, ref repositories) in &targets {
+        for repository in repositories {
+            queries.push(BatchedQuery::new(config, repository, category, tc, api_base)?);
+        }
+    }
+
+    // Indices into `queries` which still have issues to fetch.
+    let mut pending: Vec<usize> = (0..queries.len()).collect();
+    while !pending.is_empty() {
+        // Each batch must only query a single GitHub instance.
+        let api_base = queries[pending[0]].api_base;
+        let (mut batch, rest): (Vec<usize>, Vec<usize>) = pending.into_iter().partition(|&i| queries[i].api_base == api_base);
+        pending = rest;
+        if batch.len() > BATCH_SIZE {
+            pending.extend(batch.drain(BATCH_SIZE..));
+        }
+
+        let query_str = make_query(&queries, &batch);
+        let mut data: HashMap<String, Option<Repository>> = clients[api_base].graphql(&query_str)?;
+
+        for i in batch {
+            let query = &mut queries[i];
+            let repository = match data.remove(&alias(i)) {
+                Some(Some(repository)) => repository,
+                _ => return Err(::WorkErr(format!("Repository not found: {}/{}", query.owner, query.name))),
+            };
+
+            let connection = repository.issues;
+            for issue in connection.nodes {
+                let mut issue: Issue = issue.into();
+                issue.repository = query.query.repository.to_owned();
+                if query.query.matches(&issue) && issues::is_wanted(config, query.tab_category, &issue) {
+                    query.issues.push(issue);
+                }
+            }
+
+            if let Some(limit) = query.query.limit {
+                if query.issues.len() >= limit {
+                    query.issues.truncate(limit);
+                    continue;
+                }
+            }
+            if connection.page_info.has_next_page {
+                query.cursor = connection.page_info.end_cursor;
+                pending.push(i);
+            }
+        }
+    }
+
+    // Merge the issues from each repository of a tab-category pair.
+    let mut lists = HashMap::new();
+    for query in queries {
+        let key = (query.tab_category.tab.clone(), query.tab_category.category.clone());
+        lists.entry(key).or_insert_with(|| (query.tab_category, vec![])).1.push(query.issues);
+    }
+
+    let mut result = IssueData::default();
+    for (key, (tc, lists)) in lists {
+        let issues = issues::merge_issues(lists, tc.max_issues);
+        if !issues.is_empty() {
+            result.issues.insert(key, issues);
+        }
+    }
+    Ok(result)
+}
+
+// The issues for a single tab-category pair from one repository, and how far
+// we've got fetching them.
+struct BatchedQuery<'a> {
+    query: IssueQuery<'a>,
+    tab_category: &'a TabCategory,
+    api_base: &'a str,
+    owner: &'a str,
+    name: &'a str,
+    cursor: Option<String>,
+    issues: Vec<Issue>,
+}
+
+impl<'a> BatchedQuery<'a> {
+    fn new(
+        config: &Config,
+        repository: &'a str,
+        category: &'a Category,
+        tab_category: &'a TabCategory,
+        api_base: &'a str,
+    ) -> ::Result<BatchedQuery<'a>> {
+        let mut parts = repository.splitn(2, '/');
+        match (parts.next(), parts.next()) {
+            (Some(owner), Some(name)) => Ok(BatchedQuery {
+                query: IssueQuery::new(config, repository, category, tab_category),
+                tab_category,
+                api_base,
+                owner,
+                name,
+                cursor: None,
+                issues: vec![],
+            }),
+            _ => Err(::WorkErr(format!("Bad repository: {}", repository))),
+        }
+    }
+}
+
+fn alias(index: usize) -> String {
+    format!("q{}", index)
+}
+
+// Make a GraphQL query with one aliased `repository` field for each query in `batch`.
+//
+// GitHub's GraphQL API treats a list of labels as 'any of', so we can ask for
+// the 'any of' labels directly. Otherwise we only ask for the first label. Any
+// other labels (and the milestone) are checked when we get the results.
+fn make_query(queries: &[BatchedQuery], batch: &[usize]) -> String {
+    let mut result = "query {\n".to_owned();
+    for &i in batch {
+        let query = &queries[i];
+        let mut args = format!("first: {}, states: OPEN", PAGE_SIZE);
+        let labels = if query.query.any_labels.is_empty() {
+            &query.query.labels[..cmp::min(1, query.query.labels.len())]
+        } else {
+            &query.query.any_labels[..]
+        };
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels.iter().map(|l| string_literal(l)).collect();
+            args.push_str(&format!(", labels: [{}]", labels.join(", ")));
+        }
+        if let Some(ref cursor) = query.cursor {
+            args.push_str(&format!(", after: {}", string_literal(cursor)));
+        }
+
+        result.push_str(&format!(
+            "  {}: repository(owner: {}, name: {}) {{\n    issues({}) {{\n{}    }}\n  }}\n",
+            alias(i),
+            string_literal(query.owner),
+            string_literal(query.name),
+            args,
+            ISSUE_FIELDS,
+        ));
+    }
+    result.push_str("}\n");
+    result
+}
+
+const ISSUE_FIELDS: &str = "      pageInfo { hasNextPage endCursor }
+      nodes {
+        number
+        url
+        title
+        body
+        state
+        assignees(first: 10) { nodes { login url } }
+        author { login url }
+        authorAssociation
+        milestone { number title }
+        comments { totalCount }
+        createdAt
+        updatedAt
+        labels(first: 100) { nodes { name color url } }
+      }
+";
+
+// JSON string escapes are valid in GraphQL strings.
+fn string_literal(s: &str) -> String {
+    serde_json::to_string(s).expect("Could not serialise string")
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    issues: IssueConnection,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueConnection {
+    #[serde(rename="pageInfo")]
+    page_info: PageInfo,
+    nodes: Vec<GraphqlIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageInfo {
+    #[serde(rename="hasNextPage")]
+    has_next_page: bool,
+    #[serde(rename="endCursor")]
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlIssue {
+    number: u32,
+    url: String,
+    title: String,
+    body: String,
+    state: String,
+    assignees: UserConnection,
+    // `null` if the author's account has been deleted.
+    author: Option<GraphqlUser>,
+    #[serde(rename="authorAssociation")]
+    author_association: String,
+    milestone: Option<Milestone>,
+    comments: TotalCount,
+    #[serde(rename="createdAt")]
+    created_at: String,
+    #[serde(rename="updatedAt")]
+    updated_at: String,
+    labels: LabelConnection,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlUser {
+    login: String,
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserConnection {
+    nodes: Vec<GraphqlUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelConnection {
+    nodes: Vec<Label>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TotalCount {
+    #[serde(rename="totalCount")]
+    total_count: u32,
+}
+
+impl From<GraphqlUser> for User {
+    fn from(user: GraphqlUser) -> User {
+        User {
+            login: user.login,
+            url: user.url,
+        }
+    }
+}
+
+// Issues from the GraphQL API are converted to match those from the REST API.
+impl From<GraphqlIssue> for Issue {
+    fn from(issue: GraphqlIssue) -> Issue {
+        Issue {
+            number: issue.number,
+            repository: String::new(),
+            url: issue.url,
+            title: issue.title,
+            body: issue.body,
+            labels: issue.labels.nodes,
+            state: issue.state.to_lowercase(),
+            assignees: issue.assignees.nodes.into_iter().map(User::from).collect(),
+            author: issue.author.map(User::from).unwrap_or_else(|| User {
+                login: "ghost".to_owned(),
+                url: "https://github.com/ghost".to_owned(),
+            }),
+            author_association: issue.author_association,
+            milestone: issue.milestone,
+            comments: issue.comments.total_count,
+            created_at: issue.created_at,
+            updated_at: issue.updated_at,
+            // The GraphQL `issues` connection never includes pull requests.
+            pull_request: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use github::DEFAULT_API_BASE;
+    use mock::{mock_config, mock_struct_data};
+
+    #[test]
+    fn test_make_query() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let category = &struct_data.categories["rustfmt"];
+        let tc = &struct_data.tab_category["foo"][0];
+        let mut query = BatchedQuery::new(&config, &category.repository, category, tc, DEFAULT_API_BASE).unwrap();
+        query.cursor = Some("abc".to_owned());
+
+        let query_str = make_query(&[query], &[0]);
+        assert!(query_str.starts_with("query {\n  q0: repository(owner: \"rust-lang-nursery\", name: \"rustfmt\") {"));
+        assert!(query_str.contains("issues(first: 100, states: OPEN, labels: [\"p-high\"], after: \"abc\")"));
+    }
+
+    #[test]
+    fn test_make_query_any_labels() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let category = &struct_data.categories["rustfmt"];
+        let mut tc = struct_data.tab_category["foo"][0].clone();
+        tc.any_labels = vec!["E-easy".to_owned(), "E-mentor".to_owned()];
+        let query = BatchedQuery::new(&config, &category.repository, category, &tc, DEFAULT_API_BASE).unwrap();
+
+        let query_str = make_query(&[query], &[0]);
+        assert!(query_str.contains("issues(first: 100, states: OPEN, labels: [\"E-easy\", \"E-mentor\"])"));
+    }
+
+    #[test]
+    fn test_bad_repository() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let category = &struct_data.categories["rustfmt"];
+        let tc = &struct_data.tab_category["foo"][0];
+        assert!(BatchedQuery::new(&config, "rustfmt", category, tc, DEFAULT_API_BASE).is_err());
+    }
+
+    #[test]
+    fn test_fetch_issues() {
+        let _data = fetch_issues(&mock_config(), &ClientState::default(), &mock_struct_data()).unwrap();
+    }
+}
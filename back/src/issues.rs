@@ -1,38 +1,507 @@
-use config::Config;
-use data::StructuralData;
-use github:: {self, Issue};
+use config::{Backend, Config};
+use data::{Category, Host, StructuralData, TabCategory};
+use github::{self, ClientState, Issue};
+use gitlab;
+use graphql;
 
 use std::collections::HashMap;
 
-pub fn fetch_issues(config: &Config, struct_data: &StructuralData) -> ::Result<IssueData> {
-    let mut result = IssueData { issues: HashMap::new() };
-    let mut client = github::Client::new(config)?;
+pub fn fetch_issues(config: &Config, client_state: &ClientState, struct_data: &StructuralData) -> ::Result<IssueData> {
+    let mut result = fetch_all(config, client_state, struct_data)?;
+    result.keys = fetch_keys(config, struct_data);
+    // Everything we still need was requested, so the rest can go.
+    client_state.evict_unused();
+    Ok(result)
+}
+
+/// Like `fetch_issues`, but the issues in `previous` are reused for any
+/// tab-category pairs which would be fetched in the same way, only the other
+/// pairs are fetched. Cached responses are kept, since the reused pairs still
+/// need them for the next full refresh.
+pub fn fetch_issues_reusing(
+    config: &Config,
+    client_state: &ClientState,
+    struct_data: &StructuralData,
+    previous: &IssueData,
+) -> ::Result<IssueData> {
+    let keys = fetch_keys(config, struct_data);
+    let reusable = |pair: &(String, String)| previous.keys.get(pair).is_some_and(|k| Some(k) == keys.get(pair));
+
+    let mut to_fetch = struct_data.clone();
+    for tcs in to_fetch.tab_category.values_mut() {
+        tcs.retain(|tc| !reusable(&(tc.tab.clone(), tc.category.clone())));
+    }
+    let mut result = fetch_all(config, client_state, &to_fetch)?;
+
+    let mut reused = 0;
+    for pair in keys.keys().filter(|&p| reusable(p)) {
+        if let Some(issues) = previous.issues.get(pair) {
+            result.issues.insert(pair.clone(), issues.clone());
+        }
+        reused += 1;
+    }
+    info!("reused issues for {} of {} tab-category pairs", reused, keys.len());
+
+    result.keys = keys;
+    Ok(result)
+}
+
+fn fetch_all(config: &Config, client_state: &ClientState, struct_data: &StructuralData) -> ::Result<IssueData> {
+    let mut result = IssueData::default();
+    if config.backend == Backend::Graphql {
+        match graphql::fetch_issues(config, client_state, struct_data) {
+            Ok(graphql_result) => {
+                // GraphQL is only used for GitHub, fetch the rest one by one.
+                result = graphql_result;
+                fetch_issues_from_sources(config, client_state, struct_data, |c| c.host != Host::GitHub, &mut result)?;
+                return Ok(result);
+            }
+            Err(e) => warn!("Fetching issues with GraphQL failed, falling back to REST: {}", e.0),
+        }
+    }
+    fetch_issues_from_sources(config, client_state, struct_data, |_| true, &mut result)?;
+    Ok(result)
+}
+
+// Describe how the issues for each tab-category pair are fetched. If this is
+// unchanged, then so are the issues (give or take changes on GitHub).
+fn fetch_keys(config: &Config, struct_data: &StructuralData) -> HashMap<(String, String), String> {
+    let mut result = HashMap::new();
+    for tcs in struct_data.tab_category.values() {
+        for tc in tcs {
+            let category = match struct_data.categories.get(&tc.category) {
+                Some(category) => category,
+                None => continue,
+            };
+            let key = format!(
+                "{:?} {:?} {} {:?} {:?} {:?} | {:?} {:?} {:?} {:?} {:?} {} {}",
+                category.repositories(),
+                category.host,
+                category.api_base(config),
+                category.labels,
+                category.any_labels,
+                category.exclude_labels,
+                tc.labels,
+                tc.any_labels,
+                tc.exclude_labels,
+                tc.milestone,
+                tc.max_issues,
+                tc.include_pull_requests,
+                tc.hide_assigned(config),
+            );
+            result.insert((tc.tab.clone(), tc.category.clone()), key);
+        }
+    }
+    result
+}
+
+/// Somewhere issues are hosted, e.g., GitHub.
+pub trait IssueSource {
+    /// Fetch the issues matching `query`, newest first. Only issues for which
+    /// `keep` returns true are returned or count towards `query.limit`.
+    fn fetch_issues(&mut self, query: &IssueQuery, keep: &dyn Fn(&Issue) -> bool) -> ::Result<Vec<Issue>>;
+
+    /// The unarchived repositories belonging to an organisation (or group),
+    /// as "owner/name".
+    fn list_repositories(&mut self, owner: &str) -> ::Result<Vec<String>>;
+}
+
+/// The repositories to fetch a category's issues from, with any `owner/*`
+/// wildcards expanded using `source`.
+pub fn expand_repositories(source: &mut dyn IssueSource, category: &Category) -> ::Result<Vec<String>> {
+    let mut result: Vec<String> = vec![];
+    for repository in category.repositories() {
+        let expanded = if let Some(owner) = repository.strip_suffix("/*") {
+            source.list_repositories(owner)?
+        } else {
+            vec![repository.to_owned()]
+        };
+        for r in expanded {
+            if !result.contains(&r) {
+                result.push(r);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Merge the issues from several repositories into one list, newest first,
+/// with at most `limit` issues.
+pub fn merge_issues(lists: Vec<Vec<Issue>>, limit: Option<usize>) -> Vec<Issue> {
+    if lists.len() == 1 {
+        return lists.into_iter().next().unwrap();
+    }
+
+    let mut result: Vec<Issue> = lists.into_iter().flatten().collect();
+    result.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    if let Some(limit) = limit {
+        result.truncate(limit);
+    }
+    result
+}
+
+/// Which issues to fetch from an `IssueSource`.
+#[derive(Clone, Debug)]
+pub struct IssueQuery<'a> {
+    /// "owner/name"
+    pub repository: &'a str,
+    /// Issues must have all of these labels.
+    pub labels: Vec<&'a str>,
+    /// Issues must have at least one of these labels (if there are any).
+    /// Sources may ignore this, see `fetch_matching`.
+    pub any_labels: Vec<&'a str>,
+    /// Issues must have none of these labels. Sources may ignore this.
+    pub exclude_labels: Vec<&'a str>,
+    pub milestone: Option<&'a str>,
+    /// The maximum number of issues to fetch.
+    pub limit: Option<usize>,
+    /// Only fetch issues without an assignee.
+    pub unassigned: bool,
+}
+
+impl<'a> IssueQuery<'a> {
+    pub fn new(config: &Config, repository: &'a str, category: &'a Category, tc: &'a TabCategory) -> IssueQuery<'a> {
+        IssueQuery {
+            repository,
+            labels: category.labels.iter().chain(tc.labels.iter()).map(|l| &**l).collect(),
+            any_labels: category.any_labels.iter().chain(tc.any_labels.iter()).map(|l| &**l).collect(),
+            exclude_labels: category.exclude_labels.iter().chain(tc.exclude_labels.iter()).map(|l| &**l).collect(),
+            milestone: tc.milestone.as_ref().map(|s| &**s),
+            limit: tc.max_issues,
+            unassigned: tc.hide_assigned(config),
+        }
+    }
+
+    /// Whether `issue` has the labels and milestone asked for.
+    pub fn matches(&self, issue: &Issue) -> bool {
+        // GitHub and GitLab match labels ignoring case, so we do too.
+        let has = |label: &str| issue.labels.iter().any(|l| l.name.eq_ignore_ascii_case(label));
+        let has_milestone = match self.milestone {
+            Some(milestone) => issue.milestone.as_ref().is_some_and(|m| m.title == milestone),
+            None => true,
+        };
+
+        self.labels.iter().all(|l| has(l))
+            && (self.any_labels.is_empty() || self.any_labels.iter().any(|l| has(l)))
+            && !self.exclude_labels.iter().any(|l| has(l))
+            && has_milestone
+    }
+}
+
+// Fetch the issues matching `query` from `source`. Sources only handle 'all of'
+// labels, so for 'any of' labels we fetch the issues for each label and merge
+// them, and excluded labels are filtered out as issues are fetched. Issues
+// which don't match, or for which `keep` returns false, are dropped before the
+// limit is applied.
+fn fetch_matching(source: &mut dyn IssueSource, query: &IssueQuery, keep: &dyn Fn(&Issue) -> bool) -> ::Result<Vec<Issue>> {
+    let keep = |i: &Issue| query.matches(i) && keep(i);
+    if query.any_labels.is_empty() {
+        source.fetch_issues(query, &keep)
+    } else {
+        let mut merged: Vec<Issue> = vec![];
+        for &label in &query.any_labels {
+            let mut sub_query = query.clone();
+            sub_query.labels.push(label);
+            sub_query.any_labels = vec![];
+            for issue in source.fetch_issues(&sub_query, &keep)? {
+                if !merged.iter().any(|i| i.number == issue.number) {
+                    merged.push(issue);
+                }
+            }
+        }
+
+        // Newest first, the same as a single query.
+        merged.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        if let Some(limit) = query.limit {
+            merged.truncate(limit);
+        }
+        Ok(merged)
+    }
+}
+
+fn make_source<'a>(config: &'a Config, client_state: &'a ClientState, host: Host, api_base: &'a str) -> ::Result<Box<dyn IssueSource + 'a>> {
+    Ok(match host {
+        Host::GitHub => Box::new(github::Client::for_api_base(config, client_state, api_base)?),
+        Host::GitLab => Box::new(gitlab::Client::new(config, client_state, api_base)?),
+    })
+}
+
+// Fetch issues for each tab-category pair whose category satisfies `filter`,
+// one request (or more, if there are many issues) per pair and repository.
+fn fetch_issues_from_sources<F>(
+    config: &Config,
+    client_state: &ClientState,
+    struct_data: &StructuralData,
+    filter: F,
+    result: &mut IssueData,
+) -> ::Result<()>
+where
+    F: Fn(&Category) -> bool,
+{
+    // One source per GitHub or GitLab instance.
+    let mut sources = HashMap::new();
     for tcs in struct_data.tab_category.values() {
         for tc in tcs {
-            let category = &struct_data.categories[&tc.category];
-            let labels = [&*category.labels, &*tc.labels].concat().join(",");
-            let issues = client.fetch_issues(&category.repository, &labels, tc.milestone.as_ref().map(|s| &**s))?;
+            // Validation should have removed these, but skip them rather than crash.
+            let category = match struct_data.categories.get(&tc.category) {
+                Some(category) => category,
+                None => {
+                    warn!("Unknown category `{}` in tab `{}`, skipping", tc.category, tc.tab);
+                    continue;
+                }
+            };
+            if !filter(category) {
+                continue;
+            }
+
+            let api_base = category.api_base(config);
+            if !sources.contains_key(api_base) {
+                sources.insert(api_base, make_source(config, client_state, category.host, api_base)?);
+            }
+            let source = sources.get_mut(api_base).unwrap();
+
+            // Unwanted issues are dropped as they are fetched, so that they
+            // don't take the place of wanted ones when limiting.
+            let keep = |i: &Issue| is_wanted(config, tc, i);
+            let mut lists = vec![];
+            for repository in expand_repositories(&mut **source, category)? {
+                let mut issues = fetch_matching(&mut **source, &IssueQuery::new(config, &repository, category, tc), &keep)?;
+                for issue in &mut issues {
+                    issue.repository = repository.clone();
+                }
+                lists.push(issues);
+            }
+            let issues = merge_issues(lists, tc.max_issues);
 
             if !issues.is_empty() {
                 result.issues.insert((tc.tab.clone(), tc.category.clone()), issues);
             }
         }
     }
-    Ok(result)
+    Ok(())
+}
+
+/// Whether `issue` should be listed for the tab-category `tc`. Pull requests
+/// are dropped unless asked for, and so are assigned issues if the
+/// tab-category (or config) says so.
+pub fn is_wanted(config: &Config, tc: &TabCategory, issue: &Issue) -> bool {
+    if issue.is_pull_request() && !tc.include_pull_requests {
+        return false;
+    }
+    if issue.is_assigned() && tc.hide_assigned(config) {
+        return false;
+    }
+    true
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct IssueData {
     pub issues: HashMap<(String, String), Vec<Issue>>,
+    /// How the issues for each tab-category pair were fetched, see `fetch_keys`.
+    pub keys: HashMap<(String, String), String>,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use mock::{mock_config, mock_struct_data};
+    use github::{Label, PullRequestLinks, User};
+    use data::Link;
+    use mock::{mock_config, mock_issue, mock_issue_data, mock_struct_data};
+
+    // A source with a fixed list of issues, served two to a page.
+    struct MockSource {
+        issues: Vec<Issue>,
+        pages: usize,
+    }
+
+    impl IssueSource for MockSource {
+        fn fetch_issues(&mut self, query: &IssueQuery, keep: &dyn Fn(&Issue) -> bool) -> ::Result<Vec<Issue>> {
+            let issues = self.issues.clone();
+            let pages = &mut self.pages;
+            github::collect_pages("0".to_owned(), query.limit, keep, |url| {
+                *pages += 1;
+                let start: usize = url.parse().unwrap();
+                let end = issues.len().min(start + 2);
+                let next = if end < issues.len() { Some(end.to_string()) } else { None };
+                Ok((issues[start..end].to_vec(), next))
+            })
+        }
+
+        fn list_repositories(&mut self, _owner: &str) -> ::Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
 
     #[test]
     fn test_fetch_isuses() {
-        let _data = fetch_issues(&mock_config(), &mock_struct_data()).unwrap();
+        let _data = fetch_issues(&mock_config(), &ClientState::default(), &mock_struct_data()).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_issues_reusing() {
+        let config = mock_config();
+        let mut struct_data = mock_struct_data();
+        let mut previous = mock_issue_data();
+        previous.keys = fetch_keys(&config, &struct_data);
+
+        // Nothing has changed, so nothing is fetched.
+        let data = fetch_issues_reusing(&config, &ClientState::default(), &struct_data, &previous).unwrap();
+        assert_eq!(data.issues[&("foo".to_owned(), "rustfmt".to_owned())][0].number, 42);
+        assert_eq!(data.issues[&("bar".to_owned(), "rustfmt".to_owned())][0].number, 43);
+        assert_eq!(data.keys, previous.keys);
+
+        // Changing the labels means fetching again, but changing a link doesn't.
+        struct_data.tab_category.get_mut("foo").unwrap()[0].labels.push("E-easy".to_owned());
+        struct_data.tab_category.get_mut("bar").unwrap()[0].link = Some(Link { text: String::new(), url: String::new() });
+        let keys = fetch_keys(&config, &struct_data);
+        assert!(keys[&("foo".to_owned(), "rustfmt".to_owned())] != previous.keys[&("foo".to_owned(), "rustfmt".to_owned())]);
+        assert_eq!(keys[&("bar".to_owned(), "rustfmt".to_owned())], previous.keys[&("bar".to_owned(), "rustfmt".to_owned())]);
+    }
+
+    #[test]
+    fn test_reload_keeps_response_cache() {
+        let mut config = mock_config();
+        // Unique to this test and process, so that test runs don't interfere.
+        let path = ::std::env::temp_dir().join(format!("find-work-test-reload-response-cache-{}.json", ::std::process::id()));
+        let cache = r#"{"https://example.com": {"etag": "\"abc\"", "last_modified": null, "body": "[]", "next": null}}"#;
+        ::std::fs::write(&path, cache).unwrap();
+        config.cache_path = Some(path.to_str().unwrap().to_owned());
+        let client_state = ClientState::load(&config);
+        ::std::fs::remove_file(&path).unwrap();
+
+        let struct_data = mock_struct_data();
+        let mut previous = mock_issue_data();
+        previous.keys = fetch_keys(&config, &struct_data);
+
+        // Nothing is requested, but the reused issues will need the cached
+        // response at the next full refresh.
+        fetch_issues_reusing(&config, &client_state, &struct_data, &previous).unwrap();
+        client_state.save(&mock_config()).unwrap();
+        assert_eq!(client_state.cached_urls(), vec!["https://example.com"]);
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let mut category = struct_data.categories["rustfmt"].clone();
+        let tc = &struct_data.tab_category["foo"][0];
+
+        let label = |name: &str| Label { url: String::new(), name: name.to_owned(), color: String::new() };
+        let mut issue = mock_issue(1);
+        issue.labels = vec![label("p-high"), label("bug"), label("E-easy")];
+        assert!(IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+
+        category.any_labels = vec!["E-easy".to_owned(), "E-mentor".to_owned()];
+        assert!(IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+        issue.labels.pop();
+        assert!(!IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+        issue.labels.push(label("E-mentor"));
+        assert!(IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+
+        category.exclude_labels = vec!["blocked".to_owned()];
+        assert!(IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+        issue.labels.push(label("blocked"));
+        assert!(!IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+
+        // Labels are matched ignoring case.
+        issue.labels = vec![label("P-High"), label("BUG"), label("e-mentor")];
+        category.exclude_labels = vec![];
+        assert!(IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+        category.exclude_labels = vec!["Bug".to_owned()];
+        assert!(!IssueQuery::new(&config, &category.repository, &category, tc).matches(&issue));
+    }
+
+    #[test]
+    fn test_merge_issues() {
+        let mut old = mock_issue(1);
+        old.repository = "tokio-rs/mio".to_owned();
+        old.created_at = "2017-09-01T00:00:00Z".to_owned();
+        let new = mock_issue(1);
+
+        let merged = merge_issues(vec![vec![old.clone()], vec![new.clone()]], None);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].repository, "rust-lang-nursery/rustfmt");
+        assert_eq!(merged[1].repository, "tokio-rs/mio");
+
+        let merged = merge_issues(vec![vec![old], vec![new]], Some(1));
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].created_at, "2017-10-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_is_wanted() {
+        let mut config = mock_config();
+        let mut tc = mock_struct_data().tab_category["foo"][0].clone();
+
+        let issue = mock_issue(1);
+        assert!(is_wanted(&config, &tc, &issue));
+
+        let mut pr = mock_issue(2);
+        pr.pull_request = Some(PullRequestLinks {});
+        assert!(!is_wanted(&config, &tc, &pr));
+        tc.include_pull_requests = true;
+        assert!(is_wanted(&config, &tc, &pr));
+
+        let mut assigned = mock_issue(3);
+        assigned.assignees.push(User { login: "nrc".to_owned(), url: String::new() });
+        assert!(is_wanted(&config, &tc, &assigned));
+        config.hide_assigned = true;
+        assert!(!is_wanted(&config, &tc, &assigned));
+        tc.hide_assigned = Some(false);
+        assert!(is_wanted(&config, &tc, &assigned));
+    }
+
+    #[test]
+    fn test_fetch_matching_wanted() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let category = &struct_data.categories["rustfmt"];
+        let mut tc = struct_data.tab_category["foo"][0].clone();
+        tc.max_issues = Some(2);
+
+        let query = IssueQuery::new(&config, &category.repository, category, &tc);
+
+        // The newest issues are pull requests, which aren't wanted.
+        let mut issues = vec![];
+        for number in 1..6 {
+            let mut issue = mock_issue(number);
+            issue.labels = query.labels.iter().map(|&l| Label { url: String::new(), name: l.to_owned(), color: String::new() }).collect();
+            if number <= 3 {
+                issue.pull_request = Some(PullRequestLinks {});
+            }
+            issues.push(issue);
+        }
+        let mut source = MockSource { issues, pages: 0 };
+
+        let fetched = fetch_matching(&mut source, &query, &|i| is_wanted(&config, &tc, i)).unwrap();
+        assert_eq!(fetched.iter().map(|i| i.number).collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(source.pages, 3);
+    }
+
+    #[test]
+    fn test_fetch_matching_excluded() {
+        let config = mock_config();
+        let struct_data = mock_struct_data();
+        let mut category = struct_data.categories["rustfmt"].clone();
+        category.exclude_labels = vec!["blocked".to_owned()];
+        let mut tc = struct_data.tab_category["foo"][0].clone();
+        tc.max_issues = Some(1);
+        let query = IssueQuery::new(&config, &category.repository, &category, &tc);
+
+        // The newest issues are excluded by a label the source ignores.
+        let mut issues = vec![];
+        for number in 1..4 {
+            let mut issue = mock_issue(number);
+            issue.labels = query.labels.iter().map(|&l| Label { url: String::new(), name: l.to_owned(), color: String::new() }).collect();
+            if number <= 2 {
+                issue.labels.push(Label { url: String::new(), name: "blocked".to_owned(), color: String::new() });
+            }
+            issues.push(issue);
+        }
+        let mut source = MockSource { issues, pages: 0 };
+
+        let fetched = fetch_matching(&mut source, &query, &|_| true).unwrap();
+        assert_eq!(fetched.iter().map(|i| i.number).collect::<Vec<_>>(), vec![3]);
     }
 }
@@ -1,29 +1,49 @@
 extern crate base64;
+extern crate brotli2;
 extern crate env_logger;
+extern crate flate2;
 extern crate futures;
 extern crate hyper;
+extern crate jsonwebtoken;
 #[macro_use]
 extern crate log;
 extern crate mime_guess;
+extern crate notify;
 extern crate reqwest;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
+extern crate url;
 
+mod auth;
 mod blob;
+mod compress;
 mod config;
 mod data;
+mod date;
+mod feed;
 mod github;
+mod gitlab;
+mod graphql;
 mod issues;
 #[cfg(test)]
 mod mock;
+mod query;
+mod search;
 mod server;
 
 use blob::Blob;
 use config::Config;
+use feed::FirstSeen;
+use github::ClientState;
+use issues::IssueData;
 use server::ServerData;
 
+use std::sync::Arc;
+
 
 #[cfg(test)]
 const TEST_USERNAME: &'static str = "nrc";
@@ -32,10 +52,18 @@ const TEST_TOKEN: &'static str = include_str!("../test-token.txt");
 
 // In seconds.
 const REFRESH_TIMEOUT: u64 = 60 * 60;
+// How long to wait for the data files to stop changing before reloading them
+// in dev mode, in milliseconds.
+const RELOAD_DELAY: u64 = 500;
 
 
 fn main() {
     env_logger::init().unwrap();
+    let mut args = std::env::args().skip(1);
+    if args.next().is_some_and(|a| a == "validate") {
+        let dir = args.next().unwrap_or_else(|| config::LOCAL_DATA_PATH.to_owned());
+        std::process::exit(validate(&dir));
+    }
     if let Err(e) = run() {
         eprintln!("An error occured: {}", e.0);
     }
@@ -59,18 +87,41 @@ fn run() -> Result<()> {
     Ok(())
 }
 
+// Check the structural data in `dir`, printing any problems. Returns the exit
+// code for the process.
+fn validate(dir: &str) -> i32 {
+    let diagnostics = data::check_local_data(dir);
+    for d in &diagnostics {
+        println!("{}", d);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.level == data::Level::Error).count();
+    println!("{} errors, {} warnings", errors, diagnostics.len() - errors);
+    if errors > 0 { 1 } else { 0 }
+}
+
 // Initialise by reading the config, then fetching data from GitHub.
 fn init() -> Result<ServerData> {
     let config = config::read_config()?;
-    let blob = make_blob(&config)?;
-    Ok(ServerData::new(config, blob))
+    let client_state = Arc::new(ClientState::load(&config));
+    let (blob, issues) = make_blob(&config, &client_state, None)?;
+    let first_seen = FirstSeen::load(&config);
+    ServerData::new(config, client_state, blob, issues, first_seen)
 }
 
-// Fetch data from GitHub and lower it into the frontend format.
-fn make_blob(config: &Config) -> Result<Blob> {
-    let struct_data = data::fetch_structural_data(config)?;
-    let issues = issues::fetch_issues(config, &struct_data)?;
-    Blob::make(&struct_data, &issues)
+// Fetch data from GitHub and lower it into the frontend format. If `previous`
+// is given, its issues are reused where the data asks for the same issues.
+fn make_blob(config: &Config, client_state: &ClientState, previous: Option<&IssueData>) -> Result<(Blob, IssueData)> {
+    let struct_data = data::fetch_structural_data(config, client_state)?;
+    let issues = match previous {
+        Some(previous) => issues::fetch_issues_reusing(config, client_state, &struct_data, previous)?,
+        None => issues::fetch_issues(config, client_state, &struct_data)?,
+    };
+    if let Err(e) = client_state.save(config) {
+        warn!("Could not save response cache: {}", e.0);
+    }
+    let blob = Blob::make(config, &struct_data, &issues)?;
+    Ok((blob, issues))
 }
 
 #[cfg(test)]
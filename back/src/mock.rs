@@ -1,6 +1,6 @@
-use config::Config;
-use data::{Category, StructuralData, Tab, TabCategory};
-use github::Issue;
+use config::{Backend, Config};
+use data::{Category, Host, StructuralData, Tab, TabCategory};
+use github::{Issue, User, DEFAULT_API_BASE};
 use issues::IssueData;
 
 use std::collections::HashMap;
@@ -8,12 +8,20 @@ use std::collections::HashMap;
 pub fn mock_config() -> Config {
     Config {
         repository: "nrc/find-work".to_owned(),
+        data_ref: None,
+        data_sources: vec![],
         username: ::TEST_USERNAME.to_owned(),
         token: ::TEST_TOKEN.to_owned(),
+        app: None,
         addr: "127.0.0.1:80".to_owned(),
         static_path: String::new(),
         index_path: String::new(),
         dev_mode: false,
+        api_base: DEFAULT_API_BASE.to_owned(),
+        api_tokens: HashMap::new(),
+        cache_path: None,
+        backend: Backend::Rest,
+        hide_assigned: false,
     }
 }
 
@@ -23,13 +31,25 @@ pub fn mock_struct_data() -> StructuralData {
             id: "foo".to_owned(),
             title: "Foo".to_owned(),
             description: "A Foo for foos".to_owned(),
+            active_from: None,
+            active_until: None,
+            hidden: false,
+            order: 0,
         }, Tab {
             id: "bar".to_owned(),
             title: "Bar".to_owned(),
             description: "A Bar for bars".to_owned(),
+            active_from: None,
+            active_until: None,
+            hidden: false,
+            order: 0,
         }],
         categories: HashMap::new(),
         tab_category: HashMap::new(),
+        tags: vec![],
+        diagnostics: vec![],
+        sha: None,
+        source: None,
     };
 
     result.categories.insert("rustfmt".to_owned(), Category {
@@ -37,7 +57,12 @@ pub fn mock_struct_data() -> StructuralData {
         title: "Rustfmt".to_owned(),
         description: String::new(),
         repository: "rust-lang-nursery/rustfmt".to_owned(),
+        repositories: vec![],
+        host: Host::GitHub,
+        api_base: None,
         labels: vec!["p-high".to_owned()],
+        any_labels: vec![],
+        exclude_labels: vec![],
         links: vec![],
         tags: vec!["a".to_owned(), "b".to_owned()],
     });
@@ -45,15 +70,25 @@ pub fn mock_struct_data() -> StructuralData {
         tab: "foo".to_owned(),
         category: "rustfmt".to_owned(),
         labels: vec!["bug".to_owned()],
+        any_labels: vec![],
+        exclude_labels: vec![],
         milestone: None,
         link: None,
+        max_issues: None,
+        include_pull_requests: false,
+        hide_assigned: None,
     }]);
     result.tab_category.insert("bar".to_owned(), vec![TabCategory {
         tab: "bar".to_owned(),
         category: "rustfmt".to_owned(),
         labels: vec!["bug".to_owned()],
+        any_labels: vec![],
+        exclude_labels: vec![],
         milestone: None,
         link: None,
+        max_issues: None,
+        include_pull_requests: false,
+        hide_assigned: None,
     }]);
 
     result
@@ -61,21 +96,33 @@ pub fn mock_struct_data() -> StructuralData {
 
 pub fn mock_issue_data() -> IssueData {
     let mut issues = HashMap::new();
-    issues.insert(("foo".to_owned(), "rustfmt".to_owned()), vec![Issue {
-        number: 42,
-        url: String::new(),
-        title: "Title".to_owned(),
-        body: "body/description".to_owned(),
-        labels: vec![],
-    }]);
-    issues.insert(("bar".to_owned(), "rustfmt".to_owned()), vec![Issue {
-        number: 43,
+    issues.insert(("foo".to_owned(), "rustfmt".to_owned()), vec![mock_issue(42)]);
+    issues.insert(("bar".to_owned(), "rustfmt".to_owned()), vec![mock_issue(43)]);
+    IssueData {
+        issues,
+        keys: HashMap::new(),
+    }
+}
+
+pub fn mock_issue(number: u32) -> Issue {
+    Issue {
+        number,
+        repository: "rust-lang-nursery/rustfmt".to_owned(),
         url: String::new(),
         title: "Title".to_owned(),
         body: "body/description".to_owned(),
         labels: vec![],
-    }]);
-    IssueData {
-        issues,
+        state: "open".to_owned(),
+        assignees: vec![],
+        author: User {
+            login: "nrc".to_owned(),
+            url: "https://github.com/nrc".to_owned(),
+        },
+        author_association: "OWNER".to_owned(),
+        milestone: None,
+        comments: 0,
+        created_at: "2017-10-01T00:00:00Z".to_owned(),
+        updated_at: "2017-10-01T00:00:00Z".to_owned(),
+        pull_request: None,
     }
 }
@@ -0,0 +1,245 @@
+// A small query language for filtering the data endpoints, e.g.,
+// `label:E-easy repo:rust-lang/* tag:lang-rust -label:blocked`.
+//
+// A query is a list of terms separated by whitespace, all of which must match.
+// A term is `field:value`, or `-field:value` to exclude matches. Values
+// containing spaces can be quoted, e.g., `label:"good first issue"`.
+
+use blob::Category;
+use github::Issue;
+
+use std::fmt;
+
+/// A parsed query.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Term {
+    negated: bool,
+    field: Field,
+    value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    // Issue fields.
+    Label,
+    /// "owner/name", may contain `*` wildcards.
+    Repo,
+    Author,
+    Milestone,
+    // Category fields.
+    Tag,
+    Category,
+}
+
+const FIELDS: [(&str, Field); 6] = [
+    ("label", Field::Label),
+    ("repo", Field::Repo),
+    ("author", Field::Author),
+    ("milestone", Field::Milestone),
+    ("tag", Field::Tag),
+    ("category", Field::Category),
+];
+
+impl Field {
+    fn is_category_field(self) -> bool {
+        self == Field::Tag || self == Field::Category
+    }
+}
+
+impl Filter {
+    /// True if the filter matches everything.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Also require categories to have each of `tags`.
+    pub fn with_tags(mut self, tags: &[String]) -> Filter {
+        self.terms.extend(tags.iter().map(|t| Term {
+            negated: false,
+            field: Field::Tag,
+            value: t.clone(),
+        }));
+        self
+    }
+
+    /// Whether the category matches, ignoring its issues.
+    pub fn matches_category(&self, category: &Category) -> bool {
+        self.terms.iter().filter(|t| t.field.is_category_field()).all(|t| {
+            let found = match t.field {
+                Field::Tag => category.tags.contains(&t.value),
+                Field::Category => category.id == t.value,
+                _ => unreachable!(),
+            };
+            found != t.negated
+        })
+    }
+
+    pub fn matches_issue(&self, issue: &Issue) -> bool {
+        self.terms.iter().filter(|t| !t.field.is_category_field()).all(|t| {
+            let found = match t.field {
+                Field::Label => issue.labels.iter().any(|l| l.name.eq_ignore_ascii_case(&t.value)),
+                Field::Repo => glob_matches(&t.value.to_lowercase(), &issue.repository.to_lowercase()),
+                Field::Author => issue.author.login.eq_ignore_ascii_case(&t.value),
+                Field::Milestone => issue.milestone.as_ref().is_some_and(|m| m.title == t.value),
+                _ => unreachable!(),
+            };
+            found != t.negated
+        })
+    }
+}
+
+/// A syntax error in a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Counted in characters from the start of the query.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad query at character {}: {}", self.position, self.message)
+    }
+}
+
+/// Parse a query, see the top of this file for the syntax.
+pub fn parse(query: &str) -> Result<Filter, ParseError> {
+    let chars: Vec<char> = query.chars().collect();
+    let error = |position, message| Err(ParseError { position, message });
+
+    let mut result = Filter::default();
+    let mut i = 0;
+    loop {
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        if i == chars.len() {
+            return Ok(result);
+        }
+
+        let start = i;
+        let negated = chars[i] == '-';
+        if negated {
+            i += 1;
+        }
+
+        let name_start = i;
+        while i < chars.len() && chars[i] != ':' && !chars[i].is_whitespace() {
+            i += 1;
+        }
+        let name: String = chars[name_start..i].iter().collect();
+        if i == chars.len() || chars[i] != ':' {
+            return error(start, format!("expected `field:value`, found `{}`", name));
+        }
+        let field = match FIELDS.iter().find(|f| f.0 == name) {
+            Some(f) => f.1,
+            None => {
+                let names: Vec<&str> = FIELDS.iter().map(|f| f.0).collect();
+                return error(name_start, format!("unknown field `{}`, expected one of {}", name, names.join(", ")));
+            }
+        };
+        i += 1;
+
+        let value_start = i;
+        let value: String = if i < chars.len() && chars[i] == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            if i == chars.len() {
+                return error(value_start, "unclosed `\"`".to_owned());
+            }
+            i += 1;
+            chars[value_start + 1..i - 1].iter().collect()
+        } else {
+            while i < chars.len() && !chars[i].is_whitespace() {
+                i += 1;
+            }
+            chars[value_start..i].iter().collect()
+        };
+        if value.is_empty() {
+            return error(value_start, format!("`{}:` needs a value", name));
+        }
+
+        result.terms.push(Term { negated, field, value });
+    }
+}
+
+// Whether `s` matches `pattern`, where `*` in the pattern matches any
+// sequence of characters.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == s;
+    }
+
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if s.len() < first.len() + last.len() || !s.starts_with(first) || !s.ends_with(last) {
+        return false;
+    }
+    let mut rest = &s[first.len()..s.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use github::Label;
+    use mock::mock_issue;
+
+    #[test]
+    fn test_parse() {
+        let filter = parse("  label:E-easy repo:rust-lang/* -label:\"good first issue\" tag:lang-rust ").unwrap();
+        assert_eq!(filter.terms, vec![
+            Term { negated: false, field: Field::Label, value: "E-easy".to_owned() },
+            Term { negated: false, field: Field::Repo, value: "rust-lang/*".to_owned() },
+            Term { negated: true, field: Field::Label, value: "good first issue".to_owned() },
+            Term { negated: false, field: Field::Tag, value: "lang-rust".to_owned() },
+        ]);
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let message = |q| parse(q).unwrap_err().to_string();
+        assert_eq!(message("label:a easy"), "bad query at character 8: expected `field:value`, found `easy`");
+        assert_eq!(message("-colour:red"), "bad query at character 1: unknown field `colour`, expected one of label, repo, author, milestone, tag, category");
+        assert_eq!(message("label:\"good first"), "bad query at character 6: unclosed `\"`");
+        assert_eq!(message("label: tag:a"), "bad query at character 6: `label:` needs a value");
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("rust-lang/*", "rust-lang/rust"));
+        assert!(!glob_matches("rust-lang/*", "rust-lang-nursery/rustfmt"));
+        assert!(glob_matches("rust-lang*/rust*", "rust-lang-nursery/rustfmt"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("a*a", "a"));
+        assert!(glob_matches("nrc/find-work", "nrc/find-work"));
+    }
+
+    #[test]
+    fn test_matches_issue() {
+        let mut issue = mock_issue(1);
+        issue.labels.push(Label { url: String::new(), name: "E-easy".to_owned(), color: String::new() });
+
+        assert!(parse("label:e-easy repo:rust-lang-nursery/*").unwrap().matches_issue(&issue));
+        assert!(!parse("-label:E-easy").unwrap().matches_issue(&issue));
+        assert!(!parse("repo:rust-lang/*").unwrap().matches_issue(&issue));
+        assert!(parse("author:nrc -milestone:1.0").unwrap().matches_issue(&issue));
+        // Category fields don't apply to issues.
+        assert!(parse("tag:foo").unwrap().matches_issue(&issue));
+    }
+}
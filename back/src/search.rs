@@ -0,0 +1,197 @@
+// Full text search over the issues in a blob.
+//
+// We build an inverted index from each word to the issues containing it when
+// the blob is made, so searching never needs to call GitHub.
+
+use blob::Blob;
+use github::Issue;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+// How much a word counts for, depending on where it is found.
+const TITLE_WEIGHT: f64 = 3.0;
+const LABEL_WEIGHT: f64 = 2.0;
+const CATEGORY_WEIGHT: f64 = 2.0;
+const BODY_WEIGHT: f64 = 1.0;
+
+/// An index of the issues in the visible tabs of a blob.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    // Where each indexed issue is in the blob, as indices of the tab, the
+    // category in the tab, and the issue in the category.
+    docs: Vec<(usize, usize, usize)>,
+    // For each word, the issues (indices into `docs`) containing it, and how
+    // much it counts for in each.
+    postings: HashMap<String, Vec<(usize, f64)>>,
+}
+
+/// An issue found by a search, with where it was found.
+#[derive(Debug, Serialize)]
+pub struct SearchResult<'a> {
+    pub tab: &'a str,
+    pub tab_title: &'a str,
+    pub category: &'a str,
+    pub category_title: &'a str,
+    pub score: f64,
+    pub issue: &'a Issue,
+}
+
+impl SearchIndex {
+    pub fn new(blob: &Blob) -> SearchIndex {
+        let mut result = SearchIndex::default();
+        for (t, tab) in blob.tabs.iter().enumerate() {
+            for (c, category) in tab.categories.iter().enumerate() {
+                for (i, issue) in category.issues.iter().enumerate() {
+                    let doc = result.docs.len();
+                    result.docs.push((t, c, i));
+
+                    let mut weights: HashMap<String, f64> = HashMap::new();
+                    {
+                        let mut add = |text: &str, weight: f64| {
+                            for word in words(text) {
+                                *weights.entry(word).or_insert(0.0) += weight;
+                            }
+                        };
+                        add(&issue.title, TITLE_WEIGHT);
+                        add(&issue.body, BODY_WEIGHT);
+                        for label in &issue.labels {
+                            add(&label.name, LABEL_WEIGHT);
+                        }
+                        add(&category.title, CATEGORY_WEIGHT);
+                    }
+
+                    for (word, weight) in weights {
+                        result.postings.entry(word).or_insert_with(Vec::new).push((doc, weight));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Find the issues containing every word in `text`, most relevant first.
+    /// `blob` must be the blob the index was made from.
+    pub fn search<'a>(&self, blob: &'a Blob, text: &str, limit: usize) -> Vec<SearchResult<'a>> {
+        let mut query = words(text);
+        query.sort();
+        query.dedup();
+        if query.is_empty() {
+            return vec![];
+        }
+
+        // The score of each issue, and how many of the words it contains.
+        let mut scores: HashMap<usize, (f64, usize)> = HashMap::new();
+        for word in &query {
+            let postings = match self.postings.get(word) {
+                Some(postings) => postings,
+                None => return vec![],
+            };
+            // Rarer words are more significant, repeated words less so.
+            let idf = (1.0 + self.docs.len() as f64 / postings.len() as f64).ln();
+            for &(doc, weight) in postings {
+                let score = scores.entry(doc).or_insert((0.0, 0));
+                score.0 += (1.0 + weight).ln() * idf;
+                score.1 += 1;
+            }
+        }
+
+        let mut found: Vec<(usize, f64)> = scores
+            .into_iter()
+            .filter(|&(_, (_, count))| count == query.len())
+            .map(|(doc, (score, _))| (doc, score))
+            .collect();
+        // Ties are broken by the order in the blob.
+        found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then(a.0.cmp(&b.0)));
+        // An issue may be in several categories, only keep its best match.
+        let mut seen = HashSet::new();
+        found.retain(|&(doc, _)| {
+            let issue = self.issue(blob, doc);
+            seen.insert((&issue.repository, issue.number))
+        });
+        found.truncate(limit);
+
+        found
+            .into_iter()
+            .map(|(doc, score)| {
+                let (t, c, _) = self.docs[doc];
+                let tab = &blob.tabs[t];
+                let category = &tab.categories[c];
+                SearchResult {
+                    tab: &tab.id,
+                    tab_title: &tab.title,
+                    category: &category.id,
+                    category_title: &category.title,
+                    score,
+                    issue: self.issue(blob, doc),
+                }
+            })
+            .collect()
+    }
+
+    fn issue<'a>(&self, blob: &'a Blob, doc: usize) -> &'a Issue {
+        let (t, c, i) = self.docs[doc];
+        &blob.tabs[t].categories[c].issues[i]
+    }
+}
+
+// Split text into lower case words, ignoring punctuation.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::{mock_config, mock_issue_data, mock_struct_data};
+
+    fn make_blob() -> Blob {
+        let mut issues = mock_issue_data();
+        issues.issues.get_mut(&("foo".to_owned(), "rustfmt".to_owned())).unwrap()[0].body = "A crash, in the body.".to_owned();
+        issues.issues.get_mut(&("bar".to_owned(), "rustfmt".to_owned())).unwrap()[0].title = "Crash!".to_owned();
+        Blob::make(&mock_config(), &mock_struct_data(), &issues).unwrap_or_else(|s| panic!("{:?}", s))
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(words("Fix E-easy crash, (again)"), vec!["fix", "e", "easy", "crash", "again"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let blob = make_blob();
+        let index = SearchIndex::new(&blob);
+
+        // Matches in the title count for more than in the body.
+        let results = index.search(&blob, "CRASH", 10);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].tab, "bar");
+        assert_eq!(results[0].issue.number, 43);
+        assert_eq!(results[1].tab, "foo");
+        assert_eq!(results[1].category_title, "Rustfmt");
+        assert!(results[0].score > results[1].score);
+
+        // Every word must match, including category titles.
+        assert_eq!(index.search(&blob, "crash description rustfmt", 10).len(), 1);
+        assert_eq!(index.search(&blob, "crash nothing", 10).len(), 0);
+        assert_eq!(index.search(&blob, "rustfmt", 1).len(), 1);
+        assert_eq!(index.search(&blob, "  ", 10).len(), 0);
+    }
+
+    #[test]
+    fn test_search_duplicates() {
+        // The same issue, in two tabs.
+        let mut issues = mock_issue_data();
+        issues.issues.get_mut(&("bar".to_owned(), "rustfmt".to_owned())).unwrap()[0].number = 42;
+        let blob = Blob::make(&mock_config(), &mock_struct_data(), &issues).unwrap_or_else(|s| panic!("{:?}", s));
+        let index = SearchIndex::new(&blob);
+
+        let results = index.search(&blob, "title", 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tab, "foo");
+        assert_eq!(results[0].issue.number, 42);
+    }
+}
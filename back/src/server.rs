@@ -1,44 +1,113 @@
-use config::Config;
+use config::{self, Config, DataSource};
 use blob::Blob;
+use compress::{self, Encoding, Precompressed};
+use date;
+use feed::{self, FirstSeen};
+use github::{ClientState, RateLimit};
+use issues::IssueData;
+use query::{self, Filter};
+use search::SearchIndex;
 
 use futures::future;
 use mime_guess::guess_mime_type;
+use notify::{self, DebouncedEvent, RecursiveMode, Watcher};
 use hyper::{self, Method, StatusCode};
-use hyper::header::ContentType;
+use hyper::header::{AcceptEncoding, CacheControl, CacheDirective, ContentEncoding, ContentType, ETag, EntityTag, HttpDate,
+                    IfModifiedSince, IfNoneMatch, LastModified};
+use hyper::mime::{self, Mime};
 use hyper::server::{Http, Request, Response, Service, NewService};
 use serde_json;
 
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use url::form_urlencoded;
+
+// The most results a search returns.
+const MAX_SEARCH_RESULTS: usize = 50;
+// How long browsers may use static files without checking they are current,
+// in seconds. Static file names don't change between versions, so keep this
+// short.
+const STATIC_MAX_AGE: u32 = 60 * 60;
 
 
 pub struct ServerData {
     pub config: Config,
+    client_state: Arc<ClientState>,
     blob: Blob,
-    file_cache: HashMap<PathBuf, Vec<u8>>,
+    // The issues in `blob`, kept so they can be reused when reloading data.
+    issues: IssueData,
+    search_index: SearchIndex,
+    // Kept between refreshes, to date the entries in feeds.
+    first_seen: FirstSeen,
+    // For all the responses made from `blob`.
+    validators: Validators,
+    // The whole blob as JSON, which is most of what we serve.
+    blob_json: Precompressed,
+    file_cache: HashMap<PathBuf, StaticFile>,
 }
 
 impl ServerData {
-    pub fn new(config: Config, blob: Blob) -> ServerData {
-        ServerData {
+    /// `first_seen` should come from the previous data (or run), if there is
+    /// any. It is saved with the new data's issues.
+    pub fn new(
+        config: Config,
+        client_state: Arc<ClientState>,
+        blob: Blob,
+        issues: IssueData,
+        mut first_seen: FirstSeen,
+    ) -> ::Result<ServerData> {
+        first_seen.record(&blob, &date::now());
+        if let Err(e) = first_seen.save(&config) {
+            warn!("Could not save first seen times: {}", e.0);
+        }
+        let blob_json = serde_json::to_vec(&blob)?;
+        // Hidden tabs aren't serialised with the blob, but can still be
+        // requested.
+        let mut bytes = blob_json.clone();
+        bytes.extend(serde_json::to_vec(&blob.hidden_tabs)?);
+        // Compressed here, so once per refresh, rather than per request. In dev
+        // mode the data is reloaded often and requested rarely, so it is
+        // compressed (more quickly) when served instead, like static files.
+        let blob_json = if config.dev_mode {
+            Precompressed::identity(blob_json)
+        } else {
+            Precompressed::new(blob_json)?
+        };
+        Ok(ServerData {
+            validators: Validators::new(&bytes, SystemTime::now()),
+            blob_json,
             config,
+            client_state,
+            search_index: SearchIndex::new(&blob),
             blob,
+            issues,
+            first_seen,
             file_cache: HashMap::new(),
-        }
+        })
     }
 }
 
 pub fn startup(data: ServerData) -> ::Result<()> {
     let config = data.config.clone();
+    let client_state = data.client_state.clone();
     let addr = config.addr.clone();
     let data = Arc::new(RwLock::new(data));
+    // Held while refreshing, so that refreshes don't overlap.
+    let refreshing = Arc::new(Mutex::new(()));
 
-    schedule_refresh(data.clone(), config.clone());
+    if config.dev_mode {
+        watch_data(data.clone(), config.clone(), client_state.clone(), refreshing.clone());
+    }
+    schedule_refresh(data.clone(), config.clone(), client_state, refreshing);
 
     println!("starting up on http://{}", addr);
     let addr = addr.parse()?;
@@ -48,24 +117,99 @@ pub fn startup(data: ServerData) -> ::Result<()> {
     Ok(())
 }
 
-fn schedule_refresh(data: Arc<RwLock<ServerData>>, config: Config) {
+fn schedule_refresh(data: Arc<RwLock<ServerData>>, config: Config, client_state: Arc<ClientState>, refreshing: Arc<Mutex<()>>) {
     // Refresh data every hour.
     thread::spawn(move || {
         loop {
             thread::sleep(Duration::from_secs(::REFRESH_TIMEOUT));
-            match ::make_blob(&config) {
-                Ok(blob) => {
-                    let new_server_data = ServerData::new(config.clone(), blob);
-                    let mut server_data = data.write().unwrap();
-                    *server_data = new_server_data;
-                }
-                Err(e) => {
-                    // FIXME we should probably do more to indicate that making the blob failed.
-                    eprintln!("Error making blob: {}", e.0);
-                }
+            refresh(&data, &config, &client_state, &refreshing, false);
+        }
+    });
+}
+
+// Watch the local data directories and refresh as soon as the data changes,
+// reusing issues where we can.
+fn watch_data(data: Arc<RwLock<ServerData>>, config: Config, client_state: Arc<ClientState>, refreshing: Arc<Mutex<()>>) {
+    let paths: Vec<String> = config.data_sources().into_iter().filter_map(|s| match s {
+        DataSource::Local { path } => Some(path),
+        _ => None,
+    }).collect();
+    if paths.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        // Events are debounced by the watcher, so saving several files at once
+        // (or an editor writing a file in several steps) only refreshes once.
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::watcher(tx, Duration::from_millis(::RELOAD_DELAY)) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!("Could not watch data: {}", e);
+                return;
+            }
+        };
+        for path in &paths {
+            if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+                warn!("Could not watch {}: {}", path, e);
             }
         }
+
+        for event in rx {
+            let changed = match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Remove(path) => vec![path],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                _ => continue,
+            };
+            // The config and caches may live in the default data directory,
+            // but they aren't data. Refreshing with the old config would be
+            // pointless, and saving the caches would refresh again.
+            if changed.iter().all(|p| !is_data(p, &config)) {
+                continue;
+            }
+            info!("data changed, refreshing");
+            refresh(&data, &config, &client_state, &refreshing, true);
+        }
+    });
+}
+
+// Whether `path` in a watched directory could be data, rather than a file
+// which the server reads or writes itself.
+fn is_data(path: &Path, config: &Config) -> bool {
+    let mut not_data = vec![PathBuf::from(config::CONFIG_PATH)];
+    if let Some(ref cache_path) = config.cache_path {
+        not_data.push(PathBuf::from(cache_path));
+        not_data.push(feed::save_path(cache_path));
+    }
+    path.file_name().is_some_and(|name| !not_data.iter().any(|p| p.file_name() == Some(name)))
+}
+
+// Rebuild the blob and replace the server's data with it. If `reuse_issues`,
+// issues already fetched are reused where possible. Only one refresh runs at a
+// time, others wait for `refreshing`, so each builds on the last one's data.
+fn refresh(data: &RwLock<ServerData>, config: &Config, client_state: &Arc<ClientState>, refreshing: &Mutex<()>, reuse_issues: bool) {
+    let _refreshing = refreshing.lock().unwrap();
+    let previous = if reuse_issues {
+        Some(data.read().unwrap().issues.clone())
+    } else {
+        None
+    };
+    let new_server_data = ::make_blob(config, client_state, previous.as_ref()).and_then(|(blob, issues)| {
+        let first_seen = data.read().unwrap().first_seen.clone();
+        ServerData::new(config.clone(), client_state.clone(), blob, issues, first_seen)
     });
+    match new_server_data {
+        Ok(new_server_data) => {
+            let mut server_data = data.write().unwrap();
+            *server_data = new_server_data;
+        }
+        Err(e) => {
+            // FIXME we should probably do more to indicate that making the blob failed.
+            eprintln!("Error making blob: {}", e.0);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -81,15 +225,36 @@ impl WorkService {
         }
 
         let path = req.path();
-        if path.starts_with("/data/") {
+        if path == "/status" || path == "/status/" {
+            Route::Status
+        } else if path.starts_with("/data/") {
             let mut tab = &path["/data/".len()..];
             if tab.ends_with('/') {
                 tab = &tab[..tab.len()-1];
             }
+            let filter = match data_filter(req.query()) {
+                Ok(filter) => filter,
+                Err(e) => return Route::BadRequest(e.to_string()),
+            };
             if tab.is_empty() {
-                Route::Data
+                Route::Data(filter)
+            } else {
+                Route::DataByTab(tab.to_owned(), filter)
+            }
+        } else if path == "/search" || path == "/search/" {
+            let text = query_values(req.query(), "text").join(" ");
+            if text.trim().is_empty() {
+                Route::BadRequest("missing `text` parameter, e.g., `/search?text=parser`".to_owned())
             } else {
-                Route::DataByTab(tab.to_owned())
+                Route::Search(text)
+            }
+        } else if path.starts_with("/feed/") && path.ends_with(".xml") {
+            let feed = &path["/feed/".len()..path.len() - ".xml".len()];
+            let mut parts = feed.splitn(2, '/');
+            let tab = parts.next().unwrap_or("").to_owned();
+            match parts.next() {
+                Some(category) if category.contains('/') => Route::Unknown,
+                category => Route::Feed(tab, category.map(|c| c.to_owned())),
             }
         } else if path.starts_with("/static/") {
             Route::Static(path["/static/".len()..].to_owned())
@@ -101,11 +266,11 @@ impl WorkService {
     }
 
     // Load file from the cache or disk.
-    fn load_file(&self, path: &Path) -> ::Result<Vec<u8>> {
+    fn load_file(&self, path: &Path, content_type: &Mime) -> ::Result<StaticFile> {
         {
             let data = self.data.read().unwrap();
-            if let Some(bytes) = data.file_cache.get(path) {
-                return Ok(bytes.clone());
+            if let Some(file) = data.file_cache.get(path) {
+                return Ok(file.clone());
             }
         }
 
@@ -114,13 +279,95 @@ impl WorkService {
         let mut bytes = vec![];
         let mut file = File::open(path)?;
         file.read_to_end(&mut bytes)?;
+        let modified = file.metadata()?.modified()?;
+        let validators = Validators::new(&bytes, modified);
+        // Files are only compressed up front if they will be cached, otherwise
+        // they are compressed when served.
+        let body = if compress::is_compressible(content_type) && !self.config.dev_mode {
+            Precompressed::new(bytes)?
+        } else {
+            Precompressed::identity(bytes)
+        };
+        let file = StaticFile { body, validators };
 
         // Don't cache files in dev mode.
         if !self.config.dev_mode {
             let mut data = self.data.write().unwrap();
-            data.file_cache.insert(path.to_owned(), bytes.clone());
+            data.file_cache.insert(path.to_owned(), file.clone());
+        }
+        Ok(file)
+    }
+
+    // Data can change whenever it is refreshed, so browsers must always check
+    // it is current.
+    fn data_cache_control() -> CacheControl {
+        CacheControl(vec![CacheDirective::NoCache])
+    }
+
+    // In dev mode static files are edited as we go, so treat them like data.
+    fn static_cache_control(&self) -> CacheControl {
+        if self.config.dev_mode {
+            Self::data_cache_control()
+        } else {
+            CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(STATIC_MAX_AGE)])
+        }
+    }
+
+    // How to encode a response with `content_type` for this request.
+    fn encoding(req: &Request, content_type: &Mime) -> Encoding {
+        if compress::is_compressible(content_type) {
+            Encoding::negotiate(req.headers().get::<AcceptEncoding>())
+        } else {
+            Encoding::Identity
+        }
+    }
+
+    // Set the caching headers for a response in `encoding`, and make it a 304
+    // if the client already has the current version. Returns true if it does.
+    fn not_modified(
+        req: &Request,
+        res: &mut Response,
+        validators: &Validators,
+        cache_control: CacheControl,
+        content_type: &Mime,
+        encoding: Encoding,
+    ) -> bool {
+        let validators = validators.encoded(encoding);
+        {
+            let headers = res.headers_mut();
+            headers.set(cache_control);
+            headers.set(ETag(validators.etag.clone()));
+            headers.set(LastModified(HttpDate::from(validators.modified)));
+            if compress::is_compressible(content_type) {
+                // Caches must keep the different encodings apart.
+                headers.set_raw("Vary", "Accept-Encoding");
+            }
+        }
+        if validators.is_current(req) {
+            debug!("Serving 304");
+            res.set_status(StatusCode::NotModified);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Set the body of a response, which has been encoded in `encoding`.
+    fn set_body(res: &mut Response, content_type: Mime, encoding: Encoding, body: ::Result<Vec<u8>>) {
+        match body {
+            Ok(body) => {
+                if let Some(encoding) = encoding.header() {
+                    res.headers_mut().set(ContentEncoding(vec![encoding]));
+                }
+                res.headers_mut().set(ContentType(content_type));
+                res.set_body(body);
+            }
+            Err(e) => {
+                res.headers_mut().remove::<ETag>();
+                res.headers_mut().remove::<LastModified>();
+                Self::make_404(res, Some(e));
+            }
         }
-        Ok(bytes)
     }
 
     fn make_404(res: &mut Response, e: Option<::WorkErr>) {
@@ -131,6 +378,14 @@ impl WorkService {
         res.headers_mut().set(ContentType::plaintext());
         res.set_body("Page not found.");
     }
+
+    fn make_400(res: &mut Response, message: &str) {
+        debug!("Serving 400: {}", message);
+
+        res.set_status(StatusCode::BadRequest);
+        res.headers_mut().set(ContentType::plaintext());
+        res.set_body(message.to_owned());
+    }
 }
 
 impl Service for WorkService {
@@ -144,68 +399,138 @@ impl Service for WorkService {
         match self.route(&req) {
             Route::Index => {
                 let path = PathBuf::from(&self.config.index_path);
-                let bytes = match self.load_file(&path) {
-                    Ok(bytes) => bytes,
+                let content_type = mime::TEXT_HTML;
+                let file = match self.load_file(&path, &content_type) {
+                    Ok(file) => file,
                     Err(e) => {
                         Self::make_404(&mut res, Some(e));
                         return Box::new(future::ok(res));
                     }
                 };
-                res.headers_mut().set(ContentType::html());
-                res.set_body(bytes);
+                let encoding = Self::encoding(&req, &content_type);
+                // The index refers to static files by name, so must be checked
+                // each time to pick up new versions of them.
+                if Self::not_modified(&req, &mut res, &file.validators, Self::data_cache_control(), &content_type, encoding) {
+                    return Box::new(future::ok(res));
+                }
+                Self::set_body(&mut res, content_type, encoding, file.body.encoded(encoding));
             }
             Route::Static(p) => {
                 let path_base = PathBuf::from(&self.config.static_path);
                 let path = path_base.join(p);
-                let bytes = match self.load_file(&path) {
-                    Ok(bytes) => bytes,
+                // mime_guess and hyper have different `Mime` types so we have to make a string and
+                // parse it. Sadness.
+                let content_type = guess_mime_type(&path)
+                    .to_string()
+                    .parse()
+                    .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+                let file = match self.load_file(&path, &content_type) {
+                    Ok(file) => file,
                     Err(e) => {
                         Self::make_404(&mut res, Some(e));
                         return Box::new(future::ok(res));
                     }
                 };
-                // mime_guess and hyper have different `Mime` types so we have to make a string and
-                // parse it. Sadness.
-                res.headers_mut().set(ContentType(guess_mime_type(path)
-                    .to_string()
-                    .parse()
-                    .unwrap_or(hyper::mime::APPLICATION_OCTET_STREAM)));
-                res.set_body(bytes);
+                let encoding = Self::encoding(&req, &content_type);
+                if Self::not_modified(&req, &mut res, &file.validators, self.static_cache_control(), &content_type, encoding) {
+                    return Box::new(future::ok(res));
+                }
+                Self::set_body(&mut res, content_type, encoding, file.body.encoded(encoding));
             }
-            Route::Data => {
+            Route::Data(ref filter) => {
+                let content_type = mime::APPLICATION_JSON;
+                let encoding = Self::encoding(&req, &content_type);
                 let blob = {
                     let data = self.data.read().unwrap();
-                    match serde_json::to_vec(&data.blob) {
-                        Ok(blob) => blob,
-                        Err(e) => {
-                            Self::make_404(&mut res, Some(e.into()));
-                            return Box::new(future::ok(res));
-                        }
+                    if Self::not_modified(&req, &mut res, &data.validators, Self::data_cache_control(), &content_type, encoding) {
+                        return Box::new(future::ok(res));
+                    }
+                    if filter.is_empty() {
+                        data.blob_json.encoded(encoding)
+                    } else {
+                        serde_json::to_vec(&data.blob.clone().filter(filter))
+                            .map_err(::WorkErr::from)
+                            .and_then(|blob| compress::encode(blob, encoding))
                     }
                 };
-                res.headers_mut().set(ContentType::json());
-                res.set_body(blob);
+                Self::set_body(&mut res, content_type, encoding, blob);
             }
-            Route::DataByTab(ref tab) => {
+            Route::DataByTab(ref tab, ref filter) => {
+                let content_type = mime::APPLICATION_JSON;
+                let encoding = Self::encoding(&req, &content_type);
                 let blob = {
                     let data = self.data.read().unwrap();
-                    let blob = match data.blob.by_tab(tab) {
-                        Ok(blob) => blob,
+                    if Self::not_modified(&req, &mut res, &data.validators, Self::data_cache_control(), &content_type, encoding) {
+                        return Box::new(future::ok(res));
+                    }
+                    match data.blob.by_tab(tab) {
+                        Ok(blob) => blob.filter(filter),
                         Err(e) => {
                             Self::make_404(&mut res, Some(e.into()));
                             return Box::new(future::ok(res));
                         }
-                    };
-                    match serde_json::to_vec(&blob) {
-                        Ok(tab) => tab,
-                        Err(e) => {
-                            Self::make_404(&mut res, Some(e.into()));
+                    }
+                };
+                let blob = serde_json::to_vec(&blob)
+                    .map_err(::WorkErr::from)
+                    .and_then(|blob| compress::encode(blob, encoding));
+                Self::set_body(&mut res, content_type, encoding, blob);
+            }
+            Route::Search(ref text) => {
+                let content_type = mime::APPLICATION_JSON;
+                let encoding = Self::encoding(&req, &content_type);
+                let results = {
+                    let data = self.data.read().unwrap();
+                    if Self::not_modified(&req, &mut res, &data.validators, Self::data_cache_control(), &content_type, encoding) {
+                        return Box::new(future::ok(res));
+                    }
+                    let results = data.search_index.search(&data.blob, text, MAX_SEARCH_RESULTS);
+                    serde_json::to_vec(&results).map_err(::WorkErr::from)
+                };
+                let results = results.and_then(|results| compress::encode(results, encoding));
+                Self::set_body(&mut res, content_type, encoding, results);
+            }
+            Route::Feed(ref tab, ref category) => {
+                let content_type = "application/atom+xml; charset=utf-8"
+                    .parse()
+                    .unwrap_or(mime::TEXT_XML);
+                let encoding = Self::encoding(&req, &content_type);
+                let feed = {
+                    let data = self.data.read().unwrap();
+                    let feed = match feed::make_feed(&data.blob, &data.first_seen, tab, category.as_ref().map(|c| &**c)) {
+                        Some(feed) => feed,
+                        None => {
+                            Self::make_404(&mut res, None);
                             return Box::new(future::ok(res));
                         }
+                    };
+                    // Feeds only change when the data does, since that is
+                    // when issues are first seen. But the data's ETag doesn't
+                    // cover the first seen times, so the feed needs its own.
+                    let validators = Validators::new(feed.as_bytes(), data.validators.modified);
+                    if Self::not_modified(&req, &mut res, &validators, Self::data_cache_control(), &content_type, encoding) {
+                        return Box::new(future::ok(res));
                     }
+                    feed
+                };
+                Self::set_body(&mut res, content_type, encoding, compress::encode(feed.into_bytes(), encoding));
+            }
+            Route::Status => {
+                let content_type = mime::APPLICATION_JSON;
+                let encoding = Self::encoding(&req, &content_type);
+                let status = {
+                    let data = self.data.read().unwrap();
+                    let status = Status {
+                        rate_limits: data.client_state.rate_limits(),
+                        retries: data.client_state.retries(),
+                    };
+                    serde_json::to_vec(&status).map_err(::WorkErr::from)
                 };
-                res.headers_mut().set(ContentType::json());
-                res.set_body(blob);
+                res.headers_mut().set(CacheControl(vec![CacheDirective::NoStore]));
+                Self::set_body(&mut res, content_type, encoding, status.and_then(|s| compress::encode(s, encoding)));
+            }
+            Route::BadRequest(ref message) => {
+                Self::make_400(&mut res, message);
             }
             Route::Unknown => {
                 Self::make_404(&mut res, None);
@@ -227,10 +552,153 @@ impl NewService for WorkService {
     }
 }
 
+// The filter for the data routes, from the `q` and `tags` parameters.
+fn data_filter(query: Option<&str>) -> Result<Filter, query::ParseError> {
+    let q = query_values(query, "q").join(" ");
+    Ok(query::parse(&q)?.with_tags(&query_list(query, "tags")))
+}
+
+// The values of the parameter `name` in a query string.
+fn query_values(query: Option<&str>, name: &str) -> Vec<String> {
+    let query = match query {
+        Some(query) => query,
+        None => return vec![],
+    };
+    form_urlencoded::parse(query.as_bytes())
+        .filter(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+        .collect()
+}
+
+// The comma separated values of the parameter `name` in a query string.
+fn query_list(query: Option<&str>, name: &str) -> Vec<String> {
+    query_values(query, name)
+        .iter()
+        .flat_map(|v| v.split(','))
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_owned())
+        .collect()
+}
+
 enum Route {
-    Data,
-    DataByTab(String),
+    // Both data routes take a filter from the query string.
+    Data(Filter),
+    DataByTab(String, Filter),
+    BadRequest(String),
+    // A tab and optionally a category in it.
+    Feed(String, Option<String>),
     Index,
+    Search(String),
     Static(String),
+    Status,
     Unknown,
 }
+
+// A file served from disk, with the validators for it.
+#[derive(Clone, Debug)]
+struct StaticFile {
+    body: Precompressed,
+    validators: Validators,
+}
+
+// What a client needs to check whether its copy of a response is current.
+#[derive(Clone, Debug)]
+struct Validators {
+    etag: EntityTag,
+    modified: SystemTime,
+}
+
+impl Validators {
+    fn new(bytes: &[u8], modified: SystemTime) -> Validators {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        // HTTP dates don't have fractions of a second, so neither can we,
+        // otherwise we'd always look newer than the client's copy.
+        let secs = modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Validators {
+            etag: EntityTag::strong(format!("{:016x}", hasher.finish())),
+            modified: UNIX_EPOCH + Duration::from_secs(secs),
+        }
+    }
+
+    // The validators for the body in `encoding`. Strong ETags must differ
+    // between encodings.
+    fn encoded(&self, encoding: Encoding) -> Validators {
+        Validators {
+            etag: EntityTag::strong(format!("{}{}", self.etag.tag(), encoding.etag_suffix())),
+            modified: self.modified,
+        }
+    }
+
+    // Whether the client's copy is current, according to the request's
+    // conditional headers. `If-None-Match` takes precedence.
+    fn is_current(&self, req: &Request) -> bool {
+        if let Some(if_none_match) = req.headers().get::<IfNoneMatch>() {
+            return match *if_none_match {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(ref tags) => tags.iter().any(|t| t.weak_eq(&self.etag)),
+            };
+        }
+        match req.headers().get::<IfModifiedSince>() {
+            Some(&IfModifiedSince(since)) => self.modified <= SystemTime::from(since),
+            None => false,
+        }
+    }
+}
+
+/// Information about the health of the server, served at `/status`.
+#[derive(Debug, Serialize)]
+struct Status {
+    rate_limits: HashMap<String, RateLimit>,
+    /// Requests to GitHub retried after a transient failure.
+    retries: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::mock_config;
+
+    #[test]
+    fn test_validators() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1506971759500);
+        let validators = Validators::new(b"blob", modified);
+        assert_eq!(validators.modified, UNIX_EPOCH + Duration::from_secs(1506971759));
+        assert_eq!(validators.etag, Validators::new(b"blob", SystemTime::now()).etag);
+        assert!(validators.etag != Validators::new(b"blob!", modified).etag);
+        assert_eq!(validators.encoded(Encoding::Identity).etag, validators.etag);
+        assert!(validators.encoded(Encoding::Gzip).etag != validators.encoded(Encoding::Brotli).etag);
+
+        let request = |if_none_match: Option<IfNoneMatch>, if_modified_since: Option<u64>| {
+            let mut req = Request::new(Method::Get, "/data".parse().unwrap());
+            if let Some(if_none_match) = if_none_match {
+                req.headers_mut().set(if_none_match);
+            }
+            if let Some(secs) = if_modified_since {
+                req.headers_mut().set(IfModifiedSince(HttpDate::from(UNIX_EPOCH + Duration::from_secs(secs))));
+            }
+            req
+        };
+        let other = EntityTag::strong("other".to_owned());
+        let weak = EntityTag::weak(validators.etag.tag().to_owned());
+
+        assert!(!validators.is_current(&request(None, None)));
+        assert!(validators.is_current(&request(Some(IfNoneMatch::Any), None)));
+        assert!(validators.is_current(&request(Some(IfNoneMatch::Items(vec![other.clone(), weak])), None)));
+        assert!(validators.is_current(&request(None, Some(1506971759))));
+        assert!(!validators.is_current(&request(None, Some(1506971758))));
+        // `If-Modified-Since` is ignored if there is an `If-None-Match`.
+        assert!(!validators.is_current(&request(Some(IfNoneMatch::Items(vec![other])), Some(1506971759))));
+    }
+
+    #[test]
+    fn test_is_data() {
+        let mut config = mock_config();
+        config.cache_path = Some("../data/response-cache.json".to_owned());
+        assert!(is_data(Path::new("/home/nrc/find-work/data/tabs.json"), &config));
+        assert!(!is_data(Path::new("/home/nrc/find-work/data/config.json"), &config));
+        assert!(!is_data(Path::new("/home/nrc/find-work/data/response-cache.json"), &config));
+        assert!(!is_data(Path::new("/home/nrc/find-work/data/response-cache-first-seen.json"), &config));
+        assert!(!is_data(Path::new("/"), &config));
+    }
+}